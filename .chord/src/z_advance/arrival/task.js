@@ -0,0 +1,28 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            arrival: {
+                rate: 20,
+                max_in_flight: 2
+            },
+            duration: 10,
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.step1 = {
+    lon: {
+        let: "{{case.origin_lon}}"
+    },
+
+    ok: {
+        assert: `
+        (str_start_with lon "121")
+        `
+    }
+}
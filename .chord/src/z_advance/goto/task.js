@@ -0,0 +1,65 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.init = {
+    n: {
+        let: 0
+    },
+
+    then: [
+        {
+            reg: {
+                n: "{{num n}}",
+                trail: ""
+            }
+        }
+    ]
+}
+
+smoking.step.inc = {
+    n: {
+        let: "{{num (num_add n 1)}}"
+    },
+
+    then: [
+        {
+            reg: {
+                n: "{{num n}}",
+                trail: "{{trail}}i"
+            }
+        }
+    ]
+}
+
+smoking.step.check = {
+    ok: {
+        let: "{{lt n 3}}"
+    },
+
+    then: [
+        {
+            cond: "(eq ok \"true\")",
+            goto: "inc"
+        }
+    ]
+}
+
+smoking.step.done = {
+    ok: {
+        assert: `
+        (all
+          (eq n 3)
+          (eq trail "iii")
+        )
+        `
+    }
+}
@@ -0,0 +1,46 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        }
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.serial = {
+    each: {
+        iter_each: {
+            iter: [0, 1, 2, 3, 4],
+            stop: "eq item 1",
+            block: {
+                v: {
+                    let: "{{item}}"
+                }
+            }
+        }
+    },
+    ok: {
+        assert: `(eq (arr_len each) 2)`
+    }
+}
+
+smoking.step.parallel = {
+    each: {
+        iter_each: {
+            iter: [0, 1, 2, 3, 4],
+            parallel: 3,
+            stop: "eq item 1",
+            block: {
+                v: {
+                    let: "{{item}}"
+                }
+            }
+        }
+    },
+    ok: {
+        assert: `(eq (arr_len each) 2)`
+    }
+}
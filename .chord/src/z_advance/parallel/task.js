@@ -0,0 +1,76 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.fail_fast = {
+    all: {
+        parallel: {
+            action: {
+                slow: {
+                    sleep: 5
+                },
+                bad: {
+                    assert: `(eq 1 2)`
+                }
+            }
+        },
+        timeout: 3,
+        expect_error: {
+            message: "^`bad` failed"
+        }
+    }
+}
+
+smoking.step.fail_all = {
+    all: {
+        parallel: {
+            action: {
+                a: {
+                    assert: `(eq 1 2)`
+                },
+                slow: {
+                    sleep: 1
+                },
+                b: {
+                    assert: `(eq 1 3)`
+                }
+            },
+            fail_fast: false
+        },
+        timeout: 3,
+        expect_error: {
+            message: "(?s)^`a` failed.*`b` failed"
+        }
+    }
+}
+
+smoking.step.ok = {
+    all: {
+        parallel: {
+            action: {
+                a: {
+                    let: 1
+                },
+                b: {
+                    let: 2
+                }
+            }
+        }
+    },
+    ok: {
+        assert: `
+        (all
+            (eq all.a 1)
+            (eq all.b 2)
+        )
+        `
+    }
+}
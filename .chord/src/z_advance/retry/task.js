@@ -0,0 +1,45 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.step1 = {
+    cv: {
+        count: {
+            init: 1
+        }
+    },
+
+    ok: {
+        assert: `
+        (gte cv 3)
+        `
+    },
+
+    retry: {
+        max: 5,
+        delay: 0
+    }
+}
+
+smoking.step.step2 = {
+    cv: {
+        count: {
+            init: 1
+        }
+    },
+
+    retry: {
+        max: 5,
+        delay: 1,
+        backoff: "exponential",
+        cond: "(lt cv 3)"
+    }
+}
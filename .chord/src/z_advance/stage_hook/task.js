@@ -0,0 +1,45 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            round: 2,
+            def: {
+                greet: "hello-{{stage.round}}"
+            },
+            pre: {
+                step: {}
+            },
+            step: {},
+            post: {
+                step: {}
+            }
+        }
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.pre.step.prepare = {
+    token: {
+        let: "token-{{stage.def.greet}}"
+    }
+}
+
+smoking.step.use = {
+    ok: {
+        assert: `
+      (all
+        (lt 0 stage.round)
+        (str_start_with stage.def.greet "hello-")
+        (str_start_with stage.pre.step.prepare.token "token-hello-")
+      )
+    `
+    }
+}
+
+smoking.post.step.clean = {
+    ok: {
+        assert: `(str_start_with stage.pre.step.prepare.token "token-hello-")`
+    }
+}
@@ -0,0 +1,45 @@
+let conf = {
+    version: "0.0.2",
+    stage: {
+        smoking: {
+            step: {}
+        }
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.init = {
+    action: {
+        cnt: {
+            let: 0
+        }
+    }
+}
+
+smoking.step.skip = {
+    if: `eq step.init.cnt 1`,
+    action: {
+        never: {
+            assert: `(eq 1 2)`
+        }
+    }
+}
+
+smoking.step.run = {
+    if: `eq step.init.cnt 0`,
+    action: {
+        cnt: {
+            let: "{{num (num_add step.init.cnt 1)}}"
+        }
+    }
+}
+
+smoking.step.check = {
+    action: {
+        ok: {
+            assert: `(eq step.run.cnt 1)`
+        }
+    }
+}
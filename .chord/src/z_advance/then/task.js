@@ -0,0 +1,66 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.init = {
+    cnt: {
+        let: 0
+    },
+
+    then: [
+        {
+            reg: {
+                cnt: "{{num cnt}}"
+            }
+        }
+    ]
+}
+
+smoking.step.count = {
+    cnt: {
+        let: "{{num (num_add cnt 1)}}"
+    },
+
+    then: [
+        {
+            cond: "(lt cnt 10)",
+            reg: {
+                cnt: "{{num cnt}}"
+            },
+            goto: "count"
+        },
+        {
+            reg: {
+                cnt: "{{num cnt}}"
+            }
+        }
+    ]
+}
+
+smoking.step.check = {
+    ok: {
+        assert: `
+        (eq cnt 10)
+        `
+    },
+
+    then: [
+        {
+            goto: "__end__"
+        }
+    ]
+}
+
+smoking.step.never = {
+    fail: {
+        assert: "false"
+    }
+}
@@ -0,0 +1,48 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.action_timeout = {
+    slow: {
+        sleep: 3,
+        timeout: 1,
+        expect_error: {
+            message: "^timeout after 1s$"
+        }
+    }
+}
+
+smoking.step.step_timeout = {
+    slow: {
+        sleep: 3,
+        expect_error: {
+            message: "^timeout after 1s$"
+        }
+    },
+
+    timeout: 1
+}
+
+smoking.step.in_time = {
+    fast: {
+        sleep: 1,
+        timeout: 3
+    },
+
+    ok: {
+        assert: `
+        (all
+            (eq step.action_timeout.slow.message "timeout after 1s")
+            (eq step.step_timeout.slow.message "timeout after 1s")
+        )
+        `
+    }
+}
@@ -0,0 +1,59 @@
+let conf = {
+    version: "0.0.1",
+    stage: {
+        smoking: {
+            step: {}
+        },
+    }
+};
+
+module.exports = () => conf;
+let smoking = conf.stage.smoking;
+
+smoking.step.caught = {
+    handled: {
+        try: {
+            block: {
+                bad: {
+                    assert: `(eq 1 2)`
+                }
+            },
+            catch: {
+                code: {
+                    let: "{{error.code}}"
+                },
+                message: {
+                    let: "{{error.message}}"
+                }
+            }
+        }
+    },
+    ok: {
+        assert: `
+        (all
+            (eq handled.code "100")
+            (eq handled.message "false")
+        )
+        `
+    }
+}
+
+smoking.step.passed = {
+    handled: {
+        try: {
+            block: {
+                n: {
+                    let: 1
+                }
+            },
+            catch: {
+                never: {
+                    assert: `(eq 1 2)`
+                }
+            }
+        }
+    },
+    ok: {
+        assert: `(eq handled.n 1)`
+    }
+}
@@ -19,8 +19,15 @@ let allTasks = [
     "typecast",
     "refpre",
     "z_advance/stage_case",
+    "z_advance/arrival",
     "z_advance/match",
-    "z_advance/while"
+    "z_advance/retry",
+    "z_advance/then",
+    "z_advance/timeout",
+    "z_advance/goto",
+    "z_advance/while",
+    "z_advance/stage_hook",
+    "z_advance/step_if", "z_advance/iter_stop", "z_advance/parallel", "z_advance/try"
 ]
 
 module.exports = (env) => {
@@ -15,6 +15,13 @@ impl Creator for AlterCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Alter {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "entries set into the context",
+            "type": ["object", "string"]
+        })
+    }
 }
 
 struct Alter {}
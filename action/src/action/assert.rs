@@ -16,6 +16,17 @@ impl Creator for AssertCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Assert {}))
     }
+
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        explain(arg)
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "condition expression without braces",
+            "type": "string"
+        })
+    }
 }
 
 struct Assert {}
@@ -23,9 +34,7 @@ struct Assert {}
 #[async_trait]
 impl Action for Assert {
     async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
-        let raw = arg.args_raw();
-        let raw = raw.as_str().ok_or(err!("100", "illegal assert"))?.trim();
-        Ok(Value::String(raw.to_string()))
+        explain(arg)
     }
 
     async fn execute(&self, chord: &dyn Chord, arg: &mut dyn Arg) -> Result<Asset, Error> {
@@ -41,3 +50,9 @@ impl Action for Assert {
         }
     }
 }
+
+fn explain(arg: &dyn Arg) -> Result<Value, Error> {
+    let raw = arg.args_raw();
+    let raw = raw.as_str().ok_or(err!("100", "illegal assert"))?.trim();
+    Ok(Value::String(raw.to_string()))
+}
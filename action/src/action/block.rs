@@ -109,6 +109,14 @@ impl Creator for BlockCreator {
             action_vec: TailDropVec::from(action_vec),
         }))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "actions run in order",
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/action" }
+        })
+    }
 }
 
 struct Block {
@@ -44,6 +44,13 @@ impl Creator for CdylibCreator {
 
         Ok(Box::new(Cdylib { lib }))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "library name",
+            "type": "string"
+        })
+    }
 }
 
 struct Cdylib {
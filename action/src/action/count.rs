@@ -21,6 +21,17 @@ impl Creator for CountCreator {
             incr,
         }))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": ["object", "null"],
+            "properties": {
+                "init": { "type": "integer", "minimum": 0 },
+                "incr": { "type": "integer", "minimum": 0 }
+            },
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Count {
@@ -15,6 +15,18 @@ impl Creator for CryptoCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Crypto {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "by": { "enum": ["md5"] },
+                "from": { "type": "string" }
+            },
+            "required": ["by", "from"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Crypto {}
@@ -30,6 +30,21 @@ impl Creator for DatabaseCreator {
         }
         return Ok(Box::new(Database { rb: None }));
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "sql": { "type": "string" },
+                "page_no": { "type": ["integer", "string"] },
+                "page_size": { "type": ["integer", "string"] },
+                "__init__": { "type": "object" }
+            },
+            "required": ["sql"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Database {
@@ -43,6 +43,19 @@ impl Creator for Docker {
             return Ok(action);
         }
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "image": { "type": "string" },
+                "cmd": { "type": "array" },
+                "parse_json_str": { "type": "boolean" }
+            },
+            "required": ["image"],
+            "additionalProperties": false
+        })
+    }
 }
 
 pub struct DockerActual {
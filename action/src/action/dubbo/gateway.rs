@@ -114,6 +114,18 @@ impl Creator for DubboCreator {
             return Ok(action);
         }
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "method": { "type": "string" },
+                "args": { "type": "array" }
+            },
+            "required": ["method", "args"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct DubboCreatorActual {
@@ -42,4 +42,8 @@ impl Creator for DubboCreator {
     async fn create(&self, chord: &dyn Chord, arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         self.delegate.create(chord, arg).await
     }
+
+    fn schema(&self) -> Value {
+        self.delegate.schema()
+    }
 }
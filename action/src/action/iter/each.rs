@@ -0,0 +1,36 @@
+use chord_core::action::prelude::*;
+
+use crate::action::iter::{iter_run, schema};
+
+pub struct IterEachCreator {}
+
+impl IterEachCreator {
+    pub async fn new(_: Option<Value>) -> Result<IterEachCreator, Error> {
+        Ok(IterEachCreator {})
+    }
+}
+
+#[async_trait]
+impl Creator for IterEachCreator {
+    async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
+        Ok(Box::new(IterEach {}))
+    }
+
+    fn schema(&self) -> Value {
+        schema("the elements of iter the block ran for", true)
+    }
+}
+
+struct IterEach {}
+
+#[async_trait]
+impl Action for IterEach {
+    async fn execute(&self, chord: &dyn Chord, arg: &mut dyn Arg) -> Result<Asset, Error> {
+        let item_vec = iter_run(chord, arg)
+            .await?
+            .into_iter()
+            .map(|elem| elem.item)
+            .collect();
+        Ok(Asset::Value(Value::Array(item_vec)))
+    }
+}
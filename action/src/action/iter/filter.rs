@@ -0,0 +1,43 @@
+use chord_core::action::prelude::*;
+
+use crate::action::iter::{iter_run, schema};
+use crate::err;
+
+pub struct IterFilterCreator {}
+
+impl IterFilterCreator {
+    pub async fn new(_: Option<Value>) -> Result<IterFilterCreator, Error> {
+        Ok(IterFilterCreator {})
+    }
+}
+
+#[async_trait]
+impl Creator for IterFilterCreator {
+    async fn create(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
+        if arg.args_raw()["cond"].is_null() {
+            return Err(err!("100", "missing cond"));
+        }
+        Ok(Box::new(IterFilter {}))
+    }
+
+    fn schema(&self) -> Value {
+        let mut schema = schema("the elements of iter for which cond is true", false);
+        schema["required"] = json!(["iter", "cond"]);
+        schema
+    }
+}
+
+struct IterFilter {}
+
+#[async_trait]
+impl Action for IterFilter {
+    async fn execute(&self, chord: &dyn Chord, arg: &mut dyn Arg) -> Result<Asset, Error> {
+        let item_vec = iter_run(chord, arg)
+            .await?
+            .into_iter()
+            .filter(|elem| elem.pass)
+            .map(|elem| elem.item)
+            .collect();
+        Ok(Asset::Value(Value::Array(item_vec)))
+    }
+}
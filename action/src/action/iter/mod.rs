@@ -1 +1,207 @@
+use futures::{stream, StreamExt};
+
+use chord_core::action::prelude::*;
+
+use crate::err;
+
+pub mod each;
+pub mod filter;
 pub mod map;
+
+struct ArgStruct<'o, 'c> {
+    origin: &'o dyn Arg,
+    context: ContextStruct,
+    chord: &'c dyn Chord,
+}
+
+impl<'o, 'c> Arg for ArgStruct<'o, 'c> {
+    fn id(&self) -> &dyn Id {
+        self.origin.id()
+    }
+
+    fn args(&self) -> Result<Value, Error> {
+        self.chord.render(self.context(), self.args_raw())
+    }
+
+    fn args_raw(&self) -> &Value {
+        &self.origin.args_raw()["block"]
+    }
+
+    fn args_init(&self) -> Option<&Value> {
+        let raw = self.args_raw();
+        if let Value::Object(obj) = raw {
+            obj.get("__init__")
+        } else {
+            None
+        }
+    }
+
+    fn context(&self) -> &dyn Context {
+        &self.context
+    }
+
+    fn context_mut(&mut self) -> &mut dyn Context {
+        &mut self.context
+    }
+}
+
+#[derive(Clone)]
+struct ContextStruct {
+    data: Map,
+}
+
+impl Context for ContextStruct {
+    fn data(&self) -> &Map {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut Map {
+        &mut self.data
+    }
+
+    fn clone(&self) -> Box<dyn Context> {
+        let ctx = Clone::clone(self);
+        Box::new(ctx)
+    }
+}
+
+/// an element of `iter` once its block ran
+struct Elem {
+    item: Value,
+    value: Value,
+    pass: bool,
+}
+
+/// schema of the args shared by the `iter_*` actions
+fn schema(description: &str, block_required: bool) -> Value {
+    let required = if block_required {
+        json!(["iter", "block"])
+    } else {
+        json!(["iter"])
+    };
+    json!({
+        "description": description,
+        "type": "object",
+        "properties": {
+            "iter": { "type": ["array", "string"] },
+            "block": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/action" }
+            },
+            "cond": { "type": "string" },
+            "parallel": { "type": "integer", "minimum": 1 },
+            "stop": { "type": "string" }
+        },
+        "required": required,
+        "additionalProperties": false
+    })
+}
+
+/// runs `block` once per element of `iter` with `item` and `index` in the context,
+/// `parallel` elements at a time, in order. once `stop` is true for an element, no further element
+/// starts, but up to `parallel - 1` elements after it may already be running, they are dropped
+/// unfinished and left out of the result.
+/// `cond` and `stop` also see `value`, the value of the block.
+async fn iter_run(chord: &dyn Chord, arg: &dyn Arg) -> Result<Vec<Elem>, Error> {
+    let args_raw = arg.args_raw();
+    let item_vec = match chord.render(arg.context(), &args_raw["iter"])? {
+        Value::Array(item_vec) => item_vec,
+        _ => return Err(err!("100", "iter must be a array")),
+    };
+    let parallel = match &args_raw["parallel"] {
+        Value::Null => 1,
+        v => v
+            .as_u64()
+            .filter(|p| *p > 0)
+            .ok_or(err!("101", "parallel must be > 0"))? as usize,
+    };
+    let cond = cond_tpl(&args_raw["cond"], "cond")?;
+    let stop = cond_tpl(&args_raw["stop"], "stop")?;
+
+    let block = match &args_raw["block"] {
+        Value::Null => None,
+        Value::Object(_) => {
+            let create_arg = ArgStruct {
+                origin: arg,
+                context: ContextStruct {
+                    data: arg.context().data().clone(),
+                },
+                chord,
+            };
+            let block = chord
+                .creator("block")
+                .ok_or(err!("102", "missing `block` action"))?
+                .create(chord, &create_arg)
+                .await?;
+            Some(block)
+        }
+        _ => return Err(err!("103", "block must be a object")),
+    };
+
+    let block = block.as_deref();
+    let mut elem_stream = stream::iter(item_vec.into_iter().enumerate())
+        .map(|(index, item)| {
+            elem_run(chord, arg, block, cond.as_ref(), stop.as_ref(), index, item)
+        })
+        .buffered(parallel);
+
+    let mut elem_vec = vec![];
+    while let Some(elem) = elem_stream.next().await {
+        let (elem, stopped) = elem?;
+        elem_vec.push(elem);
+        if stopped {
+            break;
+        }
+    }
+    Ok(elem_vec)
+}
+
+async fn elem_run(
+    chord: &dyn Chord,
+    origin: &dyn Arg,
+    block: Option<&dyn Action>,
+    cond: Option<&Value>,
+    stop: Option<&Value>,
+    index: usize,
+    item: Value,
+) -> Result<(Elem, bool), Error> {
+    let mut data = origin.context().data().clone();
+    data.insert("item".to_string(), item.clone());
+    data.insert("index".to_string(), Value::Number(Number::from(index)));
+    let mut arg = ArgStruct {
+        origin,
+        context: ContextStruct { data },
+        chord,
+    };
+
+    let value = match block {
+        Some(block) => block.execute(chord, &mut arg).await?.to_value(),
+        None => Value::Null,
+    };
+    arg.context.data.insert("value".to_string(), value.clone());
+
+    let pass = match cond {
+        Some(cond) => is_true(&chord.render(&arg.context, cond)?),
+        None => true,
+    };
+    let stopped = match stop {
+        Some(stop) => is_true(&chord.render(&arg.context, stop)?),
+        None => false,
+    };
+    Ok((Elem { item, value, pass }, stopped))
+}
+
+fn cond_tpl(raw: &Value, name: &str) -> Result<Option<Value>, Error> {
+    match raw {
+        Value::Null => Ok(None),
+        Value::String(cond) => Ok(Some(Value::String(format!(
+            "{{{{{cond}}}}}",
+            cond = cond.trim()
+        )))),
+        _ => Err(err!("104", format!("{} must be a string", name))),
+    }
+}
+
+fn is_true(cv: &Value) -> bool {
+    cv.is_string() && cv.as_str().unwrap().eq("true")
+}
@@ -14,6 +14,12 @@ impl Creator for LetCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Let {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "values rendered in order, each one sees the ones before it"
+        })
+    }
 }
 
 struct Let {}
@@ -15,6 +15,12 @@ impl Creator for LogCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Log {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "value to log"
+        })
+    }
 }
 
 struct Log {}
@@ -19,6 +19,13 @@ impl Creator for LuaCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(LuaAction {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "lua code",
+            "type": "string"
+        })
+    }
 }
 
 struct LuaAction {}
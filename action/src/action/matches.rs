@@ -55,6 +55,17 @@ impl Creator for MatchCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Match {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "actions run under the first true condition",
+            "type": "object",
+            "additionalProperties": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/action" }
+            }
+        })
+    }
 }
 
 #[async_trait]
@@ -4,13 +4,15 @@ use chord_core::action::prelude::*;
 
 mod assert;
 mod count;
-// mod iter;
+mod iter;
 mod alter;
 mod block;
 mod lets;
 mod log;
 mod matches;
+mod parallel;
 mod sleep;
+mod tries;
 mod whiles;
 
 #[cfg(feature = "act_cdylib")]
@@ -62,6 +64,11 @@ impl CreatorComposite {
         register!(table, config_ref, "block", block::BlockCreator::new);
         register!(table, config_ref, "while", whiles::WhileCreator::new);
         register!(table, config_ref, "match", matches::MatchCreator::new);
+        register!(table, config_ref, "iter_map", iter::map::IterMapCreator::new);
+        register!(table, config_ref, "iter_filter", iter::filter::IterFilterCreator::new);
+        register!(table, config_ref, "iter_each", iter::each::IterEachCreator::new);
+        register!(table, config_ref, "parallel", parallel::ParallelCreator::new);
+        register!(table, config_ref, "try", tries::TryCreator::new);
         register!(table, config_ref, "assert", assert::AssertCreator::new);
         register!(table, config_ref, "sleep", sleep::SleepCreator::new);
         register!(table, config_ref, "log", log::LogCreator::new);
@@ -19,6 +19,21 @@ impl Creator for MongodbCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Mongodb {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "database": { "type": "string" },
+                "collection": { "type": "string" },
+                "operation": { "enum": ["insert_many"] },
+                "arg": { "type": "string" }
+            },
+            "required": ["url", "database", "collection", "operation", "arg"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Mongodb {}
@@ -0,0 +1,186 @@
+use futures::future::join_all;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+
+use chord_core::action::prelude::*;
+use chord_core::collection::TailDropVec;
+
+use crate::err;
+
+struct ArgStruct<'o, 'ch> {
+    parallel: &'o dyn Arg,
+    context: ContextStruct,
+    aid: String,
+    action: String,
+    chord: &'ch dyn Chord,
+}
+
+impl<'o, 'ch> Arg for ArgStruct<'o, 'ch> {
+    fn id(&self) -> &dyn Id {
+        self.parallel.id()
+    }
+
+    fn args(&self) -> Result<Value, Error> {
+        self.chord.render(self.context(), self.args_raw())
+    }
+
+    fn args_raw(&self) -> &Value {
+        &self.parallel.args_raw()["action"][&self.aid][&self.action]
+    }
+
+    fn args_init(&self) -> Option<&Value> {
+        let raw = self.args_raw();
+        if let Value::Object(obj) = raw {
+            obj.get("__init__")
+        } else {
+            None
+        }
+    }
+
+    fn context(&self) -> &dyn Context {
+        &self.context
+    }
+
+    fn context_mut(&mut self) -> &mut dyn Context {
+        &mut self.context
+    }
+}
+
+#[derive(Clone)]
+struct ContextStruct {
+    data: Map,
+}
+
+impl Context for ContextStruct {
+    fn data(&self) -> &Map {
+        &self.data
+    }
+
+    fn data_mut(&mut self) -> &mut Map {
+        &mut self.data
+    }
+
+    fn clone(&self) -> Box<dyn Context> {
+        let ctx = Clone::clone(self);
+        Box::new(ctx)
+    }
+}
+
+pub struct ParallelCreator {}
+
+impl ParallelCreator {
+    pub async fn new(_: Option<Value>) -> Result<ParallelCreator, Error> {
+        Ok(ParallelCreator {})
+    }
+}
+
+#[async_trait]
+impl Creator for ParallelCreator {
+    async fn create(&self, chord: &dyn Chord, arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
+        let args_raw = arg.args_raw();
+        let map = args_raw["action"]
+            .as_object()
+            .ok_or_else(|| err!("100", "action must be a object"))?;
+        let fail_fast = match &args_raw["fail_fast"] {
+            Value::Null => true,
+            Value::Bool(b) => *b,
+            _ => return Err(err!("101", "fail_fast must be a bool")),
+        };
+
+        let mut action_vec = Vec::with_capacity(map.len());
+        for (aid, fo) in map {
+            let only = fo
+                .as_object()
+                .and_then(|o| o.iter().next_back())
+                .ok_or_else(|| err!("102", format!("action `{}` must be a object", aid)))?;
+            let action = only.0.as_str();
+
+            let create_arg = ArgStruct {
+                parallel: arg,
+                context: ContextStruct {
+                    data: arg.context().data().clone(),
+                },
+                aid: aid.to_string(),
+                action: action.to_string(),
+                chord,
+            };
+
+            let action_obj = chord
+                .creator(action)
+                .ok_or_else(|| err!("103", format!("unsupported action `{}`", action)))?
+                .create(chord, &create_arg)
+                .await?;
+            action_vec.push((aid.to_string(), action.to_string(), action_obj));
+        }
+
+        Ok(Box::new(Parallel {
+            action_vec: TailDropVec::from(action_vec),
+            fail_fast,
+        }))
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "actions run at the same time, each on a copy of the context",
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "object",
+                    "additionalProperties": { "$ref": "#/$defs/action" }
+                },
+                "fail_fast": { "type": "boolean" }
+            },
+            "required": ["action"],
+            "additionalProperties": false
+        })
+    }
+}
+
+struct Parallel {
+    action_vec: TailDropVec<(String, String, Box<dyn Action>)>,
+    fail_fast: bool,
+}
+
+#[async_trait]
+impl Action for Parallel {
+    async fn execute(&self, chord: &dyn Chord, arg: &mut dyn Arg) -> Result<Asset, Error> {
+        let origin: &dyn Arg = arg;
+        let future_vec = self.action_vec.iter().map(|(aid, action, action_obj)| async move {
+            let mut run = ArgStruct {
+                parallel: origin,
+                context: ContextStruct {
+                    data: origin.context().data().clone(),
+                },
+                aid: aid.to_string(),
+                action: action.to_string(),
+                chord,
+            };
+            (aid.as_str(), action_obj.execute(chord, &mut run).await)
+        });
+
+        let mut value = Map::new();
+        if self.fail_fast {
+            // the first error drops the actions still running
+            let mut future_unordered: FuturesUnordered<_> = future_vec.collect();
+            while let Some((aid, asset)) = future_unordered.next().await {
+                let asset = asset.map_err(|e| err!("104", format!("`{}` failed:\n{}", aid, e)))?;
+                value.insert(aid.to_string(), asset.to_value());
+            }
+        } else {
+            let mut failed_vec = vec![];
+            for (aid, asset) in join_all(future_vec).await {
+                match asset {
+                    Ok(asset) => {
+                        value.insert(aid.to_string(), asset.to_value());
+                    }
+                    Err(e) => failed_vec.push(format!("`{}` failed:\n{}", aid, e)),
+                }
+            }
+            if !failed_vec.is_empty() {
+                return Err(err!("104", failed_vec.join("\n")));
+            }
+        }
+
+        Ok(Asset::Value(Value::Object(value)))
+    }
+}
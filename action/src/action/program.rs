@@ -25,6 +25,28 @@ impl Creator for ProgramCreator {
             false => Ok(Box::new(AttachProgram::new(&args_raw)?)),
         }
     }
+
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        let args = arg.args()?;
+        let command = program_command_explain(&args)?;
+        Ok(Value::String(command))
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "cmd": { "type": "array", "minItems": 1 },
+                "detach": { "type": "boolean" },
+                "boundary": { "type": "string" },
+                "content_type": {
+                    "enum": ["text/plain", "application/json", "application/chord-frame-1.0"]
+                }
+            },
+            "required": ["cmd"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct AttachProgram {}
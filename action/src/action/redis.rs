@@ -30,6 +30,20 @@ impl Creator for RedisCreator {
 
         return Ok(Box::new(Redis { client: None }));
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "cmd": { "type": "string" },
+                "args": { "type": ["array", "string"] },
+                "__init__": { "type": "object" }
+            },
+            "required": ["cmd"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Redis {
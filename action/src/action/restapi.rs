@@ -27,6 +27,27 @@ impl Creator for RestapiCreator {
             client: self.client.clone(),
         }))
     }
+
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        explain(arg)
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "method": { "type": "string" },
+                "header": {
+                    "type": "object",
+                    "additionalProperties": { "type": ["string", "array"] }
+                },
+                "body": {}
+            },
+            "required": ["url", "method"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct RestapiAction {
@@ -44,52 +65,57 @@ impl Action for RestapiAction {
     }
 
     async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
-        let args = arg.args()?;
-        let url = args["url"].as_str().ok_or(err!("100", "missing url"))?;
-
-        let url = Url::from_str(url).map_err(|_| err!("101", format!("invalid url: {}", url)))?;
-        let method = args["method"]
-            .as_str()
-            .ok_or(err!("102", "missing method"))?
-            .to_string();
-
-        let mut headers = Vec::new();
-        headers.push((
-            "Content-Type".to_string(),
-            "application/json; charset=utf-8".to_string(),
-        ));
-        if let Some(header) = args["header"].as_object() {
-            for (k, v) in header.iter() {
-                match v {
-                    Value::String(v) => {
-                        headers.push((k.clone(), v.clone()));
-                    }
-                    Value::Array(vs) => {
-                        for v in vs {
-                            headers.push((k.clone(), v.to_string()));
-                        }
-                    }
-                    _ => Err(err!("106", "invalid header value"))?,
-                };
-            }
-        };
-        let body_raw = args["body"].borrow();
-        let mut body = None;
-        if !body_raw.is_null() {
-            body = Some(body_raw.clone());
-        };
-
-        let curl = Curl {
-            method,
-            url,
-            headers,
-            body,
-        };
-
-        Ok(Value::String(curl.to_string()))
+        explain(arg)
     }
 }
 
+/// the request as a curl command
+fn explain(arg: &dyn Arg) -> Result<Value, Error> {
+    let args = arg.args()?;
+    let url = args["url"].as_str().ok_or(err!("100", "missing url"))?;
+
+    let url = Url::from_str(url).map_err(|_| err!("101", format!("invalid url: {}", url)))?;
+    let method = args["method"]
+        .as_str()
+        .ok_or(err!("102", "missing method"))?
+        .to_string();
+
+    let mut headers = Vec::new();
+    headers.push((
+        "Content-Type".to_string(),
+        "application/json; charset=utf-8".to_string(),
+    ));
+    if let Some(header) = args["header"].as_object() {
+        for (k, v) in header.iter() {
+            match v {
+                Value::String(v) => {
+                    headers.push((k.clone(), v.clone()));
+                }
+                Value::Array(vs) => {
+                    for v in vs {
+                        headers.push((k.clone(), v.to_string()));
+                    }
+                }
+                _ => Err(err!("106", "invalid header value"))?,
+            };
+        }
+    };
+    let body_raw = args["body"].borrow();
+    let mut body = None;
+    if !body_raw.is_null() {
+        body = Some(body_raw.clone());
+    };
+
+    let curl = Curl {
+        method,
+        url,
+        headers,
+        body,
+    };
+
+    Ok(Value::String(curl.to_string()))
+}
+
 async fn run(client: Client, arg: &dyn Arg) -> Result<Asset, Error> {
     let value = run0(client, arg).await?;
     Ok(Asset::Value(value))
@@ -18,6 +18,14 @@ impl Creator for SleepCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Sleep {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "seconds",
+            "type": ["integer", "string"],
+            "minimum": 1
+        })
+    }
 }
 
 struct Sleep {}
@@ -0,0 +1,167 @@
+use chord_core::action::error_code_message;
+use chord_core::action::prelude::*;
+
+use crate::err;
+
+pub struct TryCreator {}
+
+impl TryCreator {
+    pub async fn new(_: Option<Value>) -> Result<TryCreator, Error> {
+        Ok(TryCreator {})
+    }
+}
+
+struct Try {}
+
+struct ArgStruct<'a, 'c> {
+    origin: &'a mut dyn Arg,
+    part: &'static str,
+    chord: &'c dyn Chord,
+}
+
+impl<'o, 'c> Arg for ArgStruct<'o, 'c> {
+    fn id(&self) -> &dyn Id {
+        self.origin.id()
+    }
+
+    fn args(&self) -> Result<Value, Error> {
+        self.chord.render(self.context(), self.args_raw())
+    }
+
+    fn args_raw(&self) -> &Value {
+        &self.origin.args_raw()[self.part]
+    }
+
+    fn args_init(&self) -> Option<&Value> {
+        let raw = self.args_raw();
+        if let Value::Object(obj) = raw {
+            obj.get("__init__")
+        } else {
+            None
+        }
+    }
+
+    fn context(&self) -> &dyn Context {
+        self.origin.context()
+    }
+
+    fn context_mut(&mut self) -> &mut dyn Context {
+        self.origin.context_mut()
+    }
+}
+
+/// value of the block, or of `catch` once it handled an error recorded in the explain
+struct TryData {
+    value: Value,
+    explain: Value,
+}
+
+impl Data for TryData {
+    fn to_value(&self) -> Value {
+        self.value.clone()
+    }
+
+    fn explain(&self) -> Option<Value> {
+        Some(self.explain.clone())
+    }
+}
+
+#[async_trait]
+impl Creator for TryCreator {
+    async fn create(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
+        let args_raw = arg.args_raw();
+        for part in ["block", "catch", "finally"] {
+            match &args_raw[part] {
+                Value::Object(_) => (),
+                Value::Null if part != "block" => (),
+                _ => return Err(err!("100", format!("{} must be a object", part))),
+            }
+        }
+        Ok(Box::new(Try {}))
+    }
+
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        Ok(explain(arg))
+    }
+
+    fn schema(&self) -> Value {
+        let block = json!({
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/action" }
+        });
+        json!({
+            "description": "actions of block, the ones of catch run with the error if it fails, the ones of finally run anyway",
+            "type": "object",
+            "properties": {
+                "block": block,
+                "catch": block,
+                "finally": block
+            },
+            "required": ["block"],
+            "additionalProperties": false
+        })
+    }
+}
+
+#[async_trait]
+impl Action for Try {
+    async fn execute(&self, chord: &dyn Chord, arg: &mut dyn Arg) -> Result<Asset, Error> {
+        let explain = explain(arg);
+        let value = match block_run(chord, arg, "block").await {
+            Ok(value) => Ok(Asset::Value(value)),
+            Err(e) if !arg.args_raw()["catch"].is_null() => {
+                let error = error_value(&e);
+                arg.context_mut()
+                    .data_mut()
+                    .insert("error".to_string(), error.clone());
+                let value = block_run(chord, arg, "catch").await;
+                arg.context_mut().data_mut().remove("error");
+                value.map(|value| {
+                    let mut explain = explain;
+                    explain["error"] = error;
+                    Asset::Data(Box::new(TryData { value, explain }))
+                })
+            }
+            Err(e) => Err(e),
+        };
+
+        if !arg.args_raw()["finally"].is_null() {
+            block_run(chord, arg, "finally").await?;
+        }
+        value
+    }
+
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        Ok(explain(arg))
+    }
+}
+
+/// the actions of block as written, their args may refer to the values of the ones before them,
+/// so they can not be rendered ahead
+fn explain(arg: &dyn Arg) -> Value {
+    json!({ "block": arg.args_raw()["block"] })
+}
+
+/// value of the `block` action run with the actions under `part`
+async fn block_run(
+    chord: &dyn Chord,
+    arg: &mut dyn Arg,
+    part: &'static str,
+) -> Result<Value, Error> {
+    let mut arg = ArgStruct {
+        origin: arg,
+        part,
+        chord,
+    };
+    let bf = chord
+        .creator("block")
+        .ok_or(err!("101", "missing `block` action"))?
+        .create(chord, &arg)
+        .await?;
+    Ok(bf.execute(chord, &mut arg).await?.to_value())
+}
+
+fn error_value(e: &Error) -> Value {
+    let (code, message) = error_code_message(e);
+    json!({ "code": code, "message": message })
+}
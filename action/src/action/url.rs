@@ -15,6 +15,18 @@ impl Creator for UrlCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(Url {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "by": { "enum": ["encode", "decode"] },
+                "from": { "type": "string" }
+            },
+            "required": ["by", "from"],
+            "additionalProperties": false
+        })
+    }
 }
 
 struct Url {}
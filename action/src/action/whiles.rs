@@ -55,6 +55,19 @@ impl Creator for WhileCreator {
     async fn create(&self, _chord: &dyn Chord, _arg: &dyn Arg) -> Result<Box<dyn Action>, Error> {
         Ok(Box::new(While {}))
     }
+
+    fn schema(&self) -> Value {
+        json!({
+            "description": "actions run in order again and again while the condition is true",
+            "type": "object",
+            "minProperties": 1,
+            "maxProperties": 1,
+            "additionalProperties": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/action" }
+            }
+        })
+    }
 }
 
 #[async_trait]
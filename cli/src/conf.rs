@@ -37,6 +37,9 @@ impl Config {
                     "dir": home_dir.join("output").to_str().unwrap().to_string()
                 }
             },
+            "checkpoint": {
+                "dir": home_dir.join("checkpoint").to_str().unwrap().to_string()
+            },
            "creator": {
                "dubbo": {
                    "mode": "gateway",
@@ -91,6 +94,10 @@ impl Config {
     pub fn reporter(&self) -> Option<&Value> {
         self.conf.get("reporter")
     }
+
+    pub fn checkpoint_dir(&self) -> PathBuf {
+        PathBuf::from(self.conf["checkpoint"]["dir"].as_str().expect("invalid conf"))
+    }
 }
 
 impl Display for Config {
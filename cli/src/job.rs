@@ -7,6 +7,7 @@ use futures::future::join_all;
 use itertools::Itertools;
 use log::error;
 use log::trace;
+use log::warn;
 
 use chord_core::flow::{Flow, ID_PATTERN};
 use chord_core::future::fs::read_dir;
@@ -14,9 +15,10 @@ use chord_core::future::path::is_dir;
 use chord_core::future::task::spawn;
 use chord_core::input::JobLoader;
 use chord_core::output::{DateTime, JobReporter, Utc};
+use chord_core::stat::Statistic;
 use chord_core::task::{TaskAsset, TaskId, TaskState};
 use chord_core::value::Value;
-use chord_flow::{App, TaskIdStruct};
+use chord_flow::{App, CancelToken, JobCheckpoint, TaskIdStruct};
 use Error::*;
 
 #[derive(thiserror::Error, Debug)]
@@ -41,12 +43,26 @@ pub enum Error {
 
     #[error("task report error: {0}\n{1}")]
     Report(String, Box<dyn StdError + Sync + Send>),
+
+    #[error("task checkpoint error: {0}\n{1}")]
+    Checkpoint(String, Box<dyn StdError + Sync + Send>),
+}
+
+/// cancellation, checkpoint and dry run shared by all tasks of a run
+#[derive(Clone)]
+pub struct RunCtrl {
+    pub cancel: CancelToken,
+    /// only when the run is checkpointed or resumed
+    pub checkpoint: Option<Arc<JobCheckpoint>>,
+    /// case count per stage to explain, no action is executed
+    pub dry_run: Option<usize>,
 }
 
 pub async fn run<P: AsRef<Path>>(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
     exec_id: String,
     job_path: P,
     job_path_is_task: bool,
@@ -56,6 +72,7 @@ pub async fn run<P: AsRef<Path>>(
             app,
             job_loader,
             job_reporter,
+            ctrl,
             exec_id,
             job_path.as_ref().to_path_buf(),
             PathBuf::new(),
@@ -66,6 +83,7 @@ pub async fn run<P: AsRef<Path>>(
             app,
             job_loader,
             job_reporter,
+            ctrl,
             exec_id,
             job_path.as_ref().to_path_buf(),
             PathBuf::new(),
@@ -84,6 +102,7 @@ async fn job_path_run_recur(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
     exec_id: String,
     root_path: PathBuf,
     job_sub_path: PathBuf,
@@ -138,6 +157,7 @@ async fn job_path_run_recur(
                     app.clone(),
                     job_loader.clone(),
                     job_reporter.clone(),
+                    ctrl.clone(),
                     exec_id.clone(),
                     root_path.clone(),
                     child_sub_path,
@@ -153,6 +173,7 @@ async fn job_path_run_recur(
                     app.clone(),
                     job_loader.clone(),
                     job_reporter.clone(),
+                    ctrl.clone(),
                     exec_id.clone(),
                     root_path.clone(),
                     child_sub_path,
@@ -171,6 +192,7 @@ async fn job_path_run_recur(
                     app.clone(),
                     job_loader.clone(),
                     job_reporter.clone(),
+                    ctrl.clone(),
                     exec_id.clone(),
                     root_path.clone(),
                     child_sub_path.clone(),
@@ -181,6 +203,7 @@ async fn job_path_run_recur(
                     app.clone(),
                     job_loader.clone(),
                     job_reporter.clone(),
+                    ctrl.clone(),
                     exec_id.clone(),
                     root_path.clone(),
                     child_sub_path.clone(),
@@ -205,6 +228,7 @@ async fn task_path_run_to_vec(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
     exec_id: String,
     root_path: PathBuf,
     task_sub_path: PathBuf,
@@ -214,6 +238,7 @@ async fn task_path_run_to_vec(
             app,
             job_loader,
             job_reporter,
+            ctrl,
             exec_id,
             root_path,
             task_sub_path,
@@ -226,6 +251,37 @@ pub async fn dir_is_task_path(path: PathBuf) -> bool {
     sub_dir_is_task_path(path, PathBuf::new()).await
 }
 
+/// sub paths of the tasks under `root_path` in name order
+#[async_recursion]
+pub async fn task_sub_path_collect(
+    root_path: &Path,
+    sub_path: PathBuf,
+    task_sub_path_vec: &mut Vec<PathBuf>,
+) -> Result<(), std::io::Error> {
+    let mut dir = read_dir(root_path.join(sub_path.as_path())).await?;
+    let mut sub_name_vec = vec![];
+    while let Some(sub_dir) = dir.next_entry().await? {
+        if !is_dir(sub_dir.path()).await {
+            continue;
+        }
+        let sub_name: String = sub_dir.file_name().to_str().unwrap_or("").into();
+        if ID_PATTERN.is_match(sub_name.as_str()) {
+            sub_name_vec.push(sub_name);
+        }
+    }
+    sub_name_vec.sort();
+
+    for sub_name in sub_name_vec {
+        let child_sub_path = sub_path.join(sub_name);
+        if dir_is_task_path(root_path.join(child_sub_path.as_path())).await {
+            task_sub_path_vec.push(child_sub_path);
+        } else {
+            task_sub_path_collect(root_path, child_sub_path, task_sub_path_vec).await?;
+        }
+    }
+    Ok(())
+}
+
 async fn sub_dir_is_task_path(root_path: PathBuf, sub_path: PathBuf) -> bool {
     let task_path = root_path.join(sub_path);
     chord_input::flow::exists(task_path, "task").await
@@ -235,6 +291,7 @@ async fn task_path_run(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
     exec_id: String,
     root_path: PathBuf,
     task_sub_path: PathBuf,
@@ -254,7 +311,14 @@ async fn task_path_run(
     chord_flow::CTX_ID
         .scope(
             id.to_string(),
-            task_path_run_scope(app, job_loader, job_reporter, task_path, id),
+            task_path_run_scope(
+                app,
+                job_loader,
+                job_reporter,
+                ctrl,
+                task_path,
+                id,
+            ),
         )
         .await
 }
@@ -263,13 +327,21 @@ async fn task_path_run_scope(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
     task_path: PathBuf,
     id: Arc<TaskIdStruct>,
 ) -> Box<dyn TaskAsset> {
     trace!("task path start {}", task_path.to_str().unwrap());
     let start = Utc::now();
-    let task_asset =
-        task_path_run_do(task_path.clone(), id.clone(), app, job_loader, job_reporter).await;
+    let task_asset = task_path_run_do(
+        task_path.clone(),
+        id.clone(),
+        app,
+        job_loader,
+        job_reporter,
+        ctrl,
+    )
+    .await;
     return match task_asset {
         Err(e) => {
             error!("task path Err {}, {}", task_path.to_str().unwrap(), e);
@@ -279,6 +351,7 @@ async fn task_path_run_scope(
                 start,
                 end,
                 state: TaskState::Err(Box::new(e)),
+                stat: Statistic::new(start),
             })
         }
         Ok(asset) => {
@@ -294,6 +367,7 @@ async fn task_path_run_do<P: AsRef<Path>>(
     app: Arc<dyn App>,
     job_loader: Arc<dyn JobLoader>,
     job_reporter: Arc<dyn JobReporter>,
+    ctrl: RunCtrl,
 ) -> Result<Box<dyn TaskAsset>, Error> {
     let task_path = Path::new(task_path.as_ref());
     let flow = chord_input::flow::load(task_path, "task")
@@ -316,11 +390,42 @@ async fn task_path_run_do<P: AsRef<Path>>(
         .await
         .map_err(|e| Report(task_id.task().to_string(), e))?;
 
+    //checkpoint, a dry run leaves it alone
+    let job_checkpoint = match ctrl.dry_run {
+        Some(_) => None,
+        None => ctrl.checkpoint.clone(),
+    };
+    let checkpoint = match job_checkpoint.as_ref() {
+        Some(job_checkpoint) => Some(
+            job_checkpoint
+                .task(task_id.task())
+                .await
+                .map_err(|e| Checkpoint(task_id.task().to_string(), e))?,
+        ),
+        None => None,
+    };
+
     //runner
-    let task_asset = chord_flow::TaskRunner::new(loader, reporter, app, flow, task_id.clone())
-        .run()
-        .await;
+    let runner = chord_flow::TaskRunner::new(
+        loader,
+        reporter,
+        app,
+        flow,
+        task_id.clone(),
+        ctrl.cancel,
+        checkpoint,
+    );
+    let runner = match ctrl.dry_run {
+        Some(case_count) => runner.dry_run(case_count),
+        None => runner,
+    };
+    let task_asset = runner.run().await;
 
+    if let (TaskState::Ok, Some(job_checkpoint)) = (task_asset.state(), job_checkpoint) {
+        if let Err(e) = job_checkpoint.task_remove(task_id.task()).await {
+            warn!("checkpoint remove Err, {}", e);
+        }
+    }
     Ok(task_asset)
 }
 
@@ -329,6 +434,7 @@ struct JobTaskAsset {
     start: DateTime<Utc>,
     end: DateTime<Utc>,
     state: TaskState,
+    stat: Statistic,
 }
 
 impl TaskAsset for JobTaskAsset {
@@ -347,4 +453,8 @@ impl TaskAsset for JobTaskAsset {
     fn state(&self) -> &TaskState {
         &self.state
     }
+
+    fn stat(&self) -> &Statistic {
+        &self.stat
+    }
 }
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use dirs;
 use itertools::Itertools;
@@ -9,21 +11,33 @@ use tracing::Level;
 use tracing_subscriber::fmt;
 
 use chord_action::CreatorComposite;
+use chord_core::action::Creator;
+use chord_core::flow::Flow;
 use chord_core::future::path::is_dir;
+use chord_core::future::signal;
+use chord_core::future::task::spawn;
 use chord_core::task::TaskState;
-use chord_core::value::Value;
+use chord_core::value::{to_string_pretty, Map, Value};
 use chord_input::load::DefaultJobLoader;
 use chord_output::report::DefaultJobReporter;
+use chord_flow::{CancelToken, CancelTrigger, JobCheckpoint};
 
-use crate::Chord::Run;
+use crate::Chord::{Migrate, Run, Schema, Validate};
 use crate::conf::Config;
-use crate::job::dir_is_task_path;
-use crate::RunError::{InputNotDir, TaskErr, TaskFail};
+use crate::job::{dir_is_task_path, RunCtrl};
+use crate::RunError::{InputNotDir, TaskCancelled, TaskErr, TaskFail};
 
 mod conf;
 mod job;
+mod migrate;
+mod validate;
 // mod logger;
 
+/// exit code when the run was cancelled by a signal and ended gracefully
+const EXIT_CANCELLED: i32 = 130;
+/// exit code when a second signal arrived before the grace period was over
+const EXIT_FORCE_QUIT: i32 = 137;
+
 #[derive(StructOpt)]
 #[structopt(name = "chord")]
 enum Chord {
@@ -36,6 +50,10 @@ enum Chord {
         #[structopt(short, long, default_value = "1")]
         exec_id: String,
 
+        /// resume the interrupted exec id, skipping finished work and appending to its reports
+        #[structopt(long, conflicts_with = "exec-id")]
+        resume: Option<String>,
+
         /// input dir
         #[structopt(short, long, parse(from_os_str), default_value = ".")]
         input: PathBuf,
@@ -47,6 +65,57 @@ enum Chord {
         /// print verbose info
         #[structopt(long)]
         verbose: bool,
+
+        /// seconds running cases may take to finish after an interrupt
+        #[structopt(long, default_value = "30")]
+        grace: u64,
+
+        /// save the progress of each task so an interrupted run can be resumed,
+        /// the checkpoint of a task is removed once it ends Ok
+        #[structopt(long)]
+        checkpoint: bool,
+
+        /// save the context of the task pre in the checkpoint as well, in plain text,
+        /// so a resumed run does not run pre again
+        #[structopt(long)]
+        checkpoint_pre: bool,
+
+        /// explain the actions of the first cases of each stage instead of executing them,
+        /// no action is created and the report goes to the exec id suffixed with `.dry`
+        #[structopt(long, conflicts_with_all = &["resume", "checkpoint"])]
+        dry_run: bool,
+
+        /// count of cases per stage in a dry run
+        #[structopt(long, default_value = "1")]
+        dry_run_case: usize,
+    },
+    /// check every task of a job without running it
+    Validate {
+        /// input dir
+        #[structopt(short, long, parse(from_os_str), default_value = ".")]
+        input: PathBuf,
+
+        /// config file path
+        #[structopt(short, long, parse(from_os_str))]
+        config: Option<PathBuf>,
+
+        /// output format
+        #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+        format: String,
+    },
+    /// rewrite task.conf files in an older flow version to the current one,
+    /// as plain json: comments, includes and substitutions are not kept,
+    /// the old file is kept as task.conf.bak
+    Migrate {
+        /// input dir
+        #[structopt(short, long, parse(from_os_str), default_value = ".")]
+        input: PathBuf,
+    },
+    /// print the json schema of task.conf, covering the args of every enabled action
+    Schema {
+        /// config file path
+        #[structopt(short, long, parse(from_os_str))]
+        config: Option<PathBuf>,
     },
 }
 
@@ -75,29 +144,113 @@ enum RunError {
 
     #[error("task error: `{0}`\n{1}")]
     TaskErr(String, String),
+
+    #[error("task cancelled: `{0}`\n{1}")]
+    TaskCancelled(String, String),
+
+    #[error("job dir error:\n{0}")]
+    JobDir(std::io::Error),
+
+    #[error("{0} error found")]
+    Invalid(usize),
+
+    #[error("{0} task not migrated")]
+    MigrateFailed(usize),
 }
 
 
 #[chord_core::future::main]
 async fn main() -> Result<(), RunError> {
     let opt = Chord::from_args();
-    match opt {
+    let result = match opt {
         Run {
             job_name,
             exec_id,
+            resume,
             input,
             config,
             verbose,
-        } => run(job_name, exec_id, input, config, verbose).await,
+            grace,
+            checkpoint,
+            checkpoint_pre,
+            dry_run,
+            dry_run_case,
+        } => {
+            let resumed = resume.is_some();
+            let exec_id = resume.unwrap_or(exec_id);
+            let run_opt = RunOpt {
+                resume: resumed,
+                checkpoint: checkpoint || resumed,
+                checkpoint_pre,
+                grace,
+                dry_run: if dry_run { Some(dry_run_case) } else { None },
+            };
+            run(job_name, exec_id, input, config, verbose, run_opt).await
+        }
+        Validate {
+            input,
+            config,
+            format,
+        } => validate(input, config, format).await,
+        Migrate { input } => migrate(input).await,
+        Schema { config } => schema(config).await,
+    };
+    if let Err(e @ TaskCancelled(..)) = result {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(EXIT_CANCELLED);
+    }
+    result
+}
+
+/// first signal cancels gracefully, the second one quits at once
+async fn cancel_on_signal(trigger: CancelTrigger, grace: Duration) {
+    signal_recv().await;
+    eprintln!(
+        "cancelling, waiting up to {:?} for running cases, interrupt again to quit",
+        grace
+    );
+    trigger.cancel(grace);
+    signal_recv().await;
+    eprintln!("quit");
+    std::process::exit(EXIT_FORCE_QUIT);
+}
+
+#[cfg(unix)]
+async fn signal_recv() {
+    use futures::future::select;
+    use signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            select(Box::pin(signal::ctrl_c()), Box::pin(terminate.recv())).await;
+        }
+        Err(_) => {
+            let _ = signal::ctrl_c().await;
+        }
     }
 }
 
+#[cfg(not(unix))]
+async fn signal_recv() {
+    let _ = signal::ctrl_c().await;
+}
+
+/// options controlling how a run goes
+struct RunOpt {
+    resume: bool,
+    checkpoint: bool,
+    checkpoint_pre: bool,
+    grace: u64,
+    dry_run: Option<usize>,
+}
+
 async fn run(
     job_name: String,
     exec_id: String,
     input: PathBuf,
     config: Option<PathBuf>,
     verbose: bool,
+    opt: RunOpt,
 ) -> Result<(), RunError> {
     let input_dir = Path::new(&input);
     if !is_dir(input_dir).await {
@@ -107,20 +260,7 @@ async fn run(
     let exec_id: String = exec_id.clone();
     let job_name = job_name.clone();
 
-    let conf_dir_path = config
-        .clone()
-        .map(|p| PathBuf::from(p))
-        .unwrap_or_else(|| PathBuf::from(dirs::home_dir().unwrap().join(".chord").join("conf")));
-
-    let conf_data = if chord_input::conf::exists(conf_dir_path.as_path(), "cmd").await {
-        chord_input::conf::load(conf_dir_path.as_path(), "cmd")
-            .await
-            .map_err(|e| RunError::Config(e))?
-    } else {
-        Value::Null
-    };
-
-    let config = Config::new(conf_data);
+    let config = config_load(config).await?;
     if verbose {
         println!("config loaded: {}", config);
     }
@@ -164,10 +304,19 @@ async fn run(
         .map_err(|e| RunError::Report(e))?;
     let job_loader = Arc::new(job_loader);
 
-    let job_reporter =
-        DefaultJobReporter::new(config.reporter(), job_name.as_str(), exec_id.as_str())
-            .await
-            .map_err(|e| RunError::Report(e))?;
+    // a dry run leaves the report of the real run alone
+    let report_exec_id = match opt.dry_run {
+        Some(_) => format!("{}.dry", exec_id),
+        None => exec_id.clone(),
+    };
+    let job_reporter = DefaultJobReporter::new(
+        config.reporter(),
+        job_name.as_str(),
+        report_exec_id.as_str(),
+        opt.resume,
+    )
+    .await
+    .map_err(|e| RunError::Report(e))?;
     let job_reporter = Arc::new(job_reporter);
 
     let app = chord_flow::app_create(
@@ -177,10 +326,33 @@ async fn run(
             .into(),
     )
         .await;
+
+    let checkpoint = if opt.checkpoint {
+        let checkpoint = JobCheckpoint::new(
+            config
+                .checkpoint_dir()
+                .join(job_name.as_str())
+                .join(exec_id.as_str()),
+            opt.resume,
+            opt.checkpoint_pre,
+        );
+        Some(Arc::new(checkpoint))
+    } else {
+        None
+    };
+
+    let (cancel_trigger, cancel) = CancelToken::channel();
+    spawn(cancel_on_signal(cancel_trigger, Duration::from_secs(opt.grace)));
+
     let task_state_vec = job::run(
         app,
         job_loader,
         job_reporter,
+        RunCtrl {
+            cancel,
+            checkpoint,
+            dry_run: opt.dry_run,
+        },
         exec_id.clone(),
         input_dir,
         path_is_task,
@@ -188,17 +360,119 @@ async fn run(
         .await
         .map_err(|e| RunError::JobErr(e))?;
     // log.drop().await;
-    let et = task_state_vec.iter().filter(|t| !t.state().is_ok()).nth(0);
+    let et = task_state_vec
+        .iter()
+        .find(|t| matches!(t.state(), TaskState::Cancelled(_)))
+        .or_else(|| task_state_vec.iter().find(|t| !t.state().is_ok()));
     return match et {
         Some(et) => match et.state() {
             TaskState::Ok => Ok(()),
             TaskState::Err(e) => Err(TaskErr(et.id().task().to_string(), e.to_string())),
             TaskState::Fail(c) => Err(TaskFail(et.id().task().to_string(), c.to_string())),
+            TaskState::Cancelled(c) => {
+                Err(TaskCancelled(et.id().task().to_string(), c.to_string()))
+            }
         },
         None => Ok(()),
     };
 }
 
+async fn validate(
+    input: PathBuf,
+    config: Option<PathBuf>,
+    format: String,
+) -> Result<(), RunError> {
+    let input_dir = Path::new(&input);
+    if !is_dir(input_dir).await {
+        return Err(InputNotDir(input_dir.to_str().unwrap().to_string()));
+    }
+    let config = config_load(config).await?;
+
+    let path_is_task = dir_is_task_path(input_dir.to_path_buf()).await;
+    let job_loader = DefaultJobLoader::new(config.loader(), input_dir, path_is_task)
+        .await
+        .map_err(RunError::Report)?;
+    let creator_map: HashMap<String, Box<dyn Creator>> =
+        CreatorComposite::new(config.creator().cloned())
+            .await
+            .map_err(RunError::ActionFactory)?
+            .into();
+
+    let diag_vec = validate::validate(&creator_map, Arc::new(job_loader), input_dir, path_is_task)
+        .await
+        .map_err(RunError::JobDir)?;
+    if format == "json" {
+        let diag_vec: Vec<Value> = diag_vec.iter().map(|d| d.to_value()).collect();
+        println!("{}", to_string_pretty(&diag_vec).unwrap());
+    } else {
+        for diag in diag_vec.iter() {
+            println!("{}", diag.to_text());
+        }
+    }
+
+    if diag_vec.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::Invalid(diag_vec.len()))
+    }
+}
+
+async fn migrate(input: PathBuf) -> Result<(), RunError> {
+    let input_dir = Path::new(&input);
+    if !is_dir(input_dir).await {
+        return Err(InputNotDir(input_dir.to_str().unwrap().to_string()));
+    }
+
+    let path_is_task = dir_is_task_path(input_dir.to_path_buf()).await;
+    let migrated_vec = migrate::migrate(input_dir, path_is_task)
+        .await
+        .map_err(RunError::JobDir)?;
+    for migrated in migrated_vec.iter() {
+        println!("{}", migrated);
+    }
+
+    let failed = migrated_vec.iter().filter(|m| m.is_failed()).count();
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(RunError::MigrateFailed(failed))
+    }
+}
+
+async fn schema(config: Option<PathBuf>) -> Result<(), RunError> {
+    let config = config_load(config).await?;
+    let creator_map: HashMap<String, Box<dyn Creator>> =
+        CreatorComposite::new(config.creator().cloned())
+            .await
+            .map_err(RunError::ActionFactory)?
+            .into();
+
+    let mut action_schema = Map::new();
+    for (func, creator) in creator_map.iter().sorted_by_key(|(func, _)| func.as_str()) {
+        action_schema.insert(func.clone(), creator.schema());
+    }
+    println!(
+        "{}",
+        to_string_pretty(&Flow::schema(&action_schema)).unwrap()
+    );
+    Ok(())
+}
+
+async fn config_load(config: Option<PathBuf>) -> Result<Config, RunError> {
+    let conf_dir_path = config
+        .unwrap_or_else(|| dirs::home_dir().unwrap().join(".chord").join("conf"));
+
+    let conf_data = if chord_input::conf::exists(conf_dir_path.as_path(), "cmd").await {
+        chord_input::conf::load(conf_dir_path.as_path(), "cmd")
+            .await
+            .map_err(RunError::Config)?
+    } else {
+        Value::Null
+    };
+
+    Ok(Config::new(conf_data))
+}
+
 impl Debug for RunError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Display::fmt(self, f)
@@ -0,0 +1,87 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use chord_core::flow::{Flow, VERSION};
+use chord_core::future::fs::{copy, write};
+use chord_core::value::to_string_pretty;
+
+use crate::job::task_sub_path_collect;
+
+/// what became of a task file
+pub enum Migrated {
+    Upgraded(PathBuf, String),
+    Current(PathBuf),
+    Failed(PathBuf, String),
+}
+
+impl Migrated {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Migrated::Failed(..))
+    }
+}
+
+impl Display for Migrated {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Migrated::Upgraded(file, from) => {
+                write!(f, "{}: {} -> {}", file.display(), from, VERSION)
+            }
+            Migrated::Current(file) => write!(f, "{}: up to date", file.display()),
+            Migrated::Failed(file, e) => write!(f, "{}: {}", file.display(), e),
+        }
+    }
+}
+
+/// rewrites every task file under `job_path` in an older flow version to the current one,
+/// the old file is kept beside it as `task.conf.bak`.
+/// the new file is plain json, comments, includes and substitutions of the old one are lost
+pub async fn migrate(
+    job_path: &Path,
+    job_path_is_task: bool,
+) -> Result<Vec<Migrated>, std::io::Error> {
+    let mut task_sub_path_vec = vec![];
+    if job_path_is_task {
+        task_sub_path_vec.push(PathBuf::new());
+    } else {
+        task_sub_path_collect(job_path, PathBuf::new(), &mut task_sub_path_vec).await?;
+    }
+
+    let mut migrated_vec = vec![];
+    for task_sub_path in task_sub_path_vec {
+        let task_path = job_path.join(task_sub_path);
+        migrated_vec.push(task_migrate(task_path.as_path()).await);
+    }
+    Ok(migrated_vec)
+}
+
+async fn task_migrate(task_path: &Path) -> Migrated {
+    let file = task_path.join("task.conf");
+    let flow = match chord_input::flow::load(task_path, "task").await {
+        Ok(flow) => flow,
+        Err(e) => return Migrated::Failed(file, e.to_string()),
+    };
+
+    let from = flow["version"].as_str().unwrap_or("").to_string();
+    if from == VERSION {
+        return Migrated::Current(file);
+    }
+    let flow = match Flow::upgrade(flow) {
+        Ok(flow) => flow,
+        Err(e) => return Migrated::Failed(file, e.to_string()),
+    };
+    if flow["version"].as_str() != Some(VERSION) {
+        return Migrated::Failed(file, format!("unknown version `{}`", from));
+    }
+
+    let text = match to_string_pretty(&flow) {
+        Ok(text) => text,
+        Err(e) => return Migrated::Failed(file, e.to_string()),
+    };
+    if let Err(e) = copy(file.as_path(), task_path.join("task.conf.bak")).await {
+        return Migrated::Failed(file, e.to_string());
+    }
+    if let Err(e) = write(file.as_path(), text + "\n").await {
+        return Migrated::Failed(file, e.to_string());
+    }
+    Migrated::Upgraded(file, from)
+}
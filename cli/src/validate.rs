@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use chord_core::action::Creator;
+use chord_core::flow::Error::*;
+use chord_core::flow::Flow;
+use chord_core::future::fs::read_to_string;
+use chord_core::input::JobLoader;
+use chord_core::schema;
+use chord_core::value::{json, Map, Value};
+use chord_flow::{template_check, TaskIdStruct};
+
+use crate::job::task_sub_path_collect;
+
+/// a problem found in a task file
+pub struct Diagnostic {
+    task: String,
+    file: PathBuf,
+    line: Option<usize>,
+    path: Option<String>,
+    message: String,
+}
+
+impl Diagnostic {
+    pub fn to_text(&self) -> String {
+        let mut text = self.file.to_str().unwrap_or("").to_string();
+        if let Some(line) = self.line {
+            text.push_str(format!(":{}", line).as_str());
+        }
+        text.push_str(": ");
+        if let Some(path) = self.path.as_ref() {
+            text.push_str(format!("{}: ", path).as_str());
+        }
+        text.push_str(self.message.as_str());
+        text
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({
+            "task": self.task,
+            "file": self.file.to_str(),
+            "line": self.line,
+            "path": self.path,
+            "message": self.message,
+        })
+    }
+}
+
+/// checks every task under `job_path` without running anything,
+/// structure first, then action names, action args against the schema of their creator
+/// and stage loaders of well-formed flows
+pub async fn validate(
+    creator_map: &HashMap<String, Box<dyn Creator>>,
+    job_loader: Arc<dyn JobLoader>,
+    job_path: &Path,
+    job_path_is_task: bool,
+) -> Result<Vec<Diagnostic>, std::io::Error> {
+    let mut task_sub_path_vec = vec![];
+    if job_path_is_task {
+        task_sub_path_vec.push(PathBuf::new());
+    } else {
+        task_sub_path_collect(job_path, PathBuf::new(), &mut task_sub_path_vec).await?;
+    }
+
+    let mut action_schema = Map::new();
+    for (func, creator) in creator_map.iter() {
+        action_schema.insert(func.clone(), creator.schema());
+    }
+    let schema_def = Flow::schema(&action_schema)["$defs"]
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+
+    let mut diag_vec = vec![];
+    for task_sub_path in task_sub_path_vec {
+        let mut task_id = task_sub_path.iter().map(|p| p.to_str().unwrap()).join(".");
+        if task_id.is_empty() {
+            task_id = job_path
+                .iter()
+                .next_back()
+                .and_then(|p| p.to_str())
+                .unwrap_or("")
+                .to_string();
+        }
+        let task_path = job_path.join(task_sub_path);
+        task_validate(
+            creator_map,
+            &schema_def,
+            job_loader.clone(),
+            task_id,
+            task_path.as_path(),
+            &mut diag_vec,
+        )
+        .await;
+    }
+    Ok(diag_vec)
+}
+
+async fn task_validate(
+    creator_map: &HashMap<String, Box<dyn Creator>>,
+    schema_def: &Map,
+    job_loader: Arc<dyn JobLoader>,
+    task_id: String,
+    task_path: &Path,
+    diag_vec: &mut Vec<Diagnostic>,
+) {
+    let file = task_path.join("task.conf");
+    let text = read_to_string(file.as_path()).await.unwrap_or_default();
+    let diag = |path: Option<String>, message: String| Diagnostic {
+        task: task_id.clone(),
+        file: file.clone(),
+        line: path.as_ref().and_then(|p| line_of(text.as_str(), p.as_str())),
+        path,
+        message,
+    };
+
+    let flow = match chord_input::flow::load(task_path, "task").await {
+        Ok(flow) => flow,
+        Err(e) => {
+            diag_vec.push(diag(None, e.to_string()));
+            return;
+        }
+    };
+
+    let err_vec = Flow::check(flow.clone(), task_path);
+    if !err_vec.is_empty() {
+        let mut task_diag_vec: Vec<Diagnostic> = err_vec
+            .into_iter()
+            .map(|e| match &e {
+                // the duplicate is the later one
+                IdDuplicated(id) => Diagnostic {
+                    line: line_of_last(text.as_str(), id.as_str()),
+                    ..diag(Some(id.clone()), e.to_string())
+                },
+                _ => diag(error_path(&e), e.to_string()),
+            })
+            .collect();
+        task_diag_vec.sort_by_key(|d| d.line);
+        diag_vec.extend(task_diag_vec);
+        return;
+    }
+    let flow = match Flow::new(flow, task_path) {
+        Ok(flow) => Arc::new(flow),
+        Err(e) => {
+            diag_vec.push(diag(error_path(&e), e.to_string()));
+            return;
+        }
+    };
+
+    let mut step_vec: Vec<(String, &str)> = flow
+        .pre_step_id_vec()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|sid| (format!("pre.step.{}", sid), sid))
+        .collect();
+    for stage_id in flow.stage_id_vec() {
+        for sid in flow.stage_pre_step_id_vec(stage_id).unwrap_or_default() {
+            step_vec.push((format!("stage.{}.pre.step.{}", stage_id, sid), sid));
+        }
+        for sid in flow.stage_step_id_vec(stage_id) {
+            step_vec.push((format!("stage.{}.step.{}", stage_id, sid), sid));
+        }
+        for sid in flow.stage_post_step_id_vec(stage_id).unwrap_or_default() {
+            step_vec.push((format!("stage.{}.post.step.{}", stage_id, sid), sid));
+        }
+    }
+    for sid in flow.post_step_id_vec().unwrap_or_default() {
+        step_vec.push((format!("post.step.{}", sid), sid));
+    }
+    for (step_path, sid) in step_vec {
+        for aid in flow.step_action_id_vec(sid) {
+            let func = flow.step_action_func(sid, aid);
+            match creator_map.get(func) {
+                Some(creator) => {
+                    let args_path = format!("{}.action.{}.{}", step_path, aid, func);
+                    let args = flow.step_action_args(sid, aid);
+                    for e in schema::check(args_path.as_str(), args, &creator.schema(), schema_def) {
+                        diag_vec.push(diag(error_path(&e), e.to_string()));
+                    }
+                }
+                None => diag_vec.push(diag(
+                    Some(format!("{}.action.{}", step_path, aid)),
+                    format!("unknown action `{}`", func),
+                )),
+            }
+        }
+        for did in flow.step_defer_id_vec(sid) {
+            let func = flow.step_defer_func(sid, did);
+            match creator_map.get(func) {
+                Some(creator) => {
+                    let args_path = format!("{}.defer.{}.{}", step_path, did, func);
+                    let args = flow.step_defer_args(sid, did);
+                    for e in schema::check(args_path.as_str(), args, &creator.schema(), schema_def) {
+                        diag_vec.push(diag(error_path(&e), e.to_string()));
+                    }
+                }
+                None => diag_vec.push(diag(
+                    Some(format!("{}.defer.{}", step_path, did)),
+                    format!("unknown action `{}`", func),
+                )),
+            }
+        }
+    }
+
+    let id = Arc::new(TaskIdStruct::new("validate".to_string(), task_id.clone()));
+    let loader = match job_loader.task(id, flow.clone()).await {
+        Ok(loader) => loader,
+        Err(e) => {
+            diag_vec.push(diag(None, format!("loader error:\n{}", e)));
+            return;
+        }
+    };
+    let mut case_field_map = HashMap::new();
+    for stage_id in flow.stage_id_vec() {
+        let fields = match loader.stage(stage_id).await {
+            Ok(mut stage_loader) => stage_loader.fields().await,
+            Err(e) => {
+                diag_vec.push(diag(
+                    Some(format!("stage.{}", stage_id)),
+                    format!("loader error: {}", e),
+                ));
+                continue;
+            }
+        };
+        if let Ok(Some(fields)) = fields {
+            case_field_map.insert(stage_id.to_string(), fields);
+        }
+    }
+
+    for e in template_check(flow.as_ref(), &case_field_map) {
+        let message = e.to_string();
+        let message = message.trim_start_matches(e.path()).trim_start().to_string();
+        diag_vec.push(diag(Some(e.path().to_string()), message));
+    }
+}
+
+/// path of the entry an error is about, in the form of `a.b.c`
+fn error_path(err: &chord_core::flow::Error) -> Option<String> {
+    let path = match err {
+        IdInvalid(id) | IdDuplicated(id) => id.clone(),
+        EntryLost(p, _) => p.clone(),
+        EntryUnexpected(p, k) => format!("{}.{}", p, k),
+        Violation(p, _, _) => p.clone(),
+        ValueUnexpected(p, _) => p.clone(),
+    };
+    if path == "root" {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// 1-based line of the last key of `path` found in order in `text`, keys not found are skipped
+fn line_of(text: &str, path: &str) -> Option<usize> {
+    let mut offset = None;
+    for key in path.split('.').filter(|k| *k != "root") {
+        let from = offset.map(|o| o + 1).unwrap_or(0);
+        if let Some(o) = key_offset(text, from, key) {
+            offset = Some(o);
+        }
+    }
+    offset.map(|o| text[..o].matches('\n').count() + 1)
+}
+
+fn line_of_last(text: &str, key: &str) -> Option<usize> {
+    let mut offset = None;
+    while let Some(o) = key_offset(text, offset.map(|o| o + 1).unwrap_or(0), key) {
+        offset = Some(o);
+    }
+    offset.map(|o| text[..o].matches('\n').count() + 1)
+}
+
+/// offset of `key` used as an entry key, followed by `:`, `=` or `{`,
+/// a key within a string value or a comment is not one
+fn key_offset(text: &str, from: usize, key: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    for (idx, _) in text[from..].match_indices(key) {
+        let idx = from + idx;
+        let before = text[..idx].chars().next_back();
+        if before.map(is_word).unwrap_or(false) {
+            continue;
+        }
+        let line_start = text[..idx].rfind('\n').map(|o| o + 1).unwrap_or(0);
+        let quoted = match quote_state(&text[line_start..idx]) {
+            QuoteState::Comment => continue,
+            QuoteState::Out => false,
+            QuoteState::In { opened_at } if opened_at + 1 == idx - line_start => true,
+            QuoteState::In { .. } => continue,
+        };
+        let after = &text[idx + key.len()..];
+        let after = if quoted {
+            match after.strip_prefix('"') {
+                Some(after) => after,
+                None => continue,
+            }
+        } else {
+            after
+        };
+        let after = after.trim_start();
+        if after.starts_with(':') || after.starts_with('=') || after.starts_with('{') {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+enum QuoteState {
+    Out,
+    In { opened_at: usize },
+    Comment,
+}
+
+/// whether the end of `line` is within a quoted string or a comment
+fn quote_state(line: &str) -> QuoteState {
+    let mut opened_at = None;
+    let mut escaped = false;
+    let mut char_iter = line.char_indices().peekable();
+    while let Some((idx, c)) = char_iter.next() {
+        if opened_at.is_none() {
+            match c {
+                '"' => opened_at = Some(idx),
+                '#' => return QuoteState::Comment,
+                '/' if char_iter.peek().map(|(_, n)| *n == '/').unwrap_or(false) => {
+                    return QuoteState::Comment
+                }
+                _ => {}
+            }
+        } else if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            opened_at = None;
+        }
+    }
+    match opened_at {
+        Some(opened_at) => QuoteState::In { opened_at },
+        None => QuoteState::Out,
+    }
+}
+
+#[test]
+fn line_of_key() {
+    let text = r#"{
+  "stage": {
+    "smoking": {
+      # step: in a comment
+      "log": "step: in a string, \"step\": escaped",
+      step = {
+        "a": { "action": { "x": { "log": "a" } } }
+      }
+    }
+  }
+}"#;
+    assert_eq!(line_of(text, "stage.smoking.step"), Some(6));
+    assert_eq!(line_of(text, "stage.smoking.step.a.action.x"), Some(7));
+    assert_eq!(line_of(text, "stage.smoking.lost"), Some(3));
+    assert_eq!(key_offset(text, 0, "in"), None);
+    assert_eq!(line_of_last(text, "log"), Some(7));
+}
+
+#[cfg(test)]
+struct Known {}
+
+#[cfg(test)]
+#[chord_core::action::async_trait]
+impl Creator for Known {
+    async fn create(
+        &self,
+        _: &dyn chord_core::action::Chord,
+        _: &dyn chord_core::action::Arg,
+    ) -> Result<Box<dyn chord_core::action::Action>, chord_core::action::Error> {
+        Err("not created in a validation".into())
+    }
+
+    fn schema(&self) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+#[cfg(test)]
+async fn fixture_validate(name: &str) -> Vec<Value> {
+    let task_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixture")
+        .join("validate")
+        .join(name);
+    let mut creator_map: HashMap<String, Box<dyn Creator>> = HashMap::new();
+    creator_map.insert("log".into(), Box::new(Known {}));
+    let job_loader = chord_input::load::DefaultJobLoader::new(None, task_path.as_path(), true)
+        .await
+        .unwrap();
+    validate(&creator_map, Arc::new(job_loader), task_path.as_path(), true)
+        .await
+        .unwrap()
+        .iter()
+        .map(|d| {
+            let mut d = d.to_value();
+            d.as_object_mut().unwrap().remove("file");
+            d
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn validate_fixture() {
+    assert_eq!(
+        fixture_validate("action_unknown").await,
+        vec![json!({
+            "task": "action_unknown",
+            "line": 10,
+            "path": "stage.smoking.step.hello.action.missing",
+            "message": "unknown action `echo`",
+        })]
+    );
+
+    assert_eq!(
+        fixture_validate("id_duplicated").await,
+        vec![json!({
+            "task": "id_duplicated",
+            "line": 15,
+            "path": "hello",
+            "message": "duplicated id `hello`",
+        })]
+    );
+
+    assert_eq!(
+        fixture_validate("args_invalid").await,
+        vec![json!({
+            "task": "args_invalid",
+            "line": 8,
+            "path": "stage.smoking.step.hello.action.content.log",
+            "message": "stage.smoking.step.hello.action.content.log must be string but it is number",
+        })]
+    );
+
+    let diag_vec = fixture_validate("csv_lost").await;
+    assert_eq!(diag_vec.len(), 1);
+    assert_eq!(diag_vec[0]["line"], json!(4));
+    assert_eq!(diag_vec[0]["path"], json!("stage.smoking"));
+    assert!(diag_vec[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("loader error:"));
+}
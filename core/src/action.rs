@@ -3,6 +3,7 @@ use std::fmt::Display;
 pub use async_trait::async_trait;
 pub use chrono::{DateTime, Utc};
 
+use crate::value::from_str;
 use crate::value::Map;
 use crate::value::Value;
 
@@ -51,6 +52,11 @@ pub trait Context: Sync + Send {
 
 pub trait Data: Sync + Send {
     fn to_value(&self) -> Value;
+
+    /// what the action did, in place of the explain given before it ran
+    fn explain(&self) -> Option<Value> {
+        None
+    }
 }
 
 impl Data for Value {
@@ -125,4 +131,43 @@ pub trait Action: Sync + Send {
 #[async_trait]
 pub trait Creator: Sync + Send {
     async fn create(&self, chord: &dyn Chord, arg: &dyn Arg) -> Result<Box<dyn Action>, Error>;
+
+    /// explains the args without creating the action, as a dry run does, so nothing may be touched
+    async fn explain(&self, _chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, Error> {
+        arg.args()
+    }
+
+    /// json schema of the args as written in the flow, any value by default
+    fn schema(&self) -> Value {
+        Value::Object(Map::new())
+    }
+}
+
+/// code and message of an action error, actions report them as a json object of `code` and
+/// `message`, the text of any other error is its message with an empty code
+pub fn error_code_message(e: &Error) -> (String, String) {
+    let text = e.to_string();
+    if let Ok(Value::Object(obj)) = from_str::<Value>(text.as_str()) {
+        if let (Some(Value::String(code)), Some(Value::String(message))) =
+            (obj.get("code"), obj.get("message"))
+        {
+            return (code.clone(), message.clone());
+        }
+    }
+    (String::new(), text)
+}
+
+#[test]
+fn code_message() {
+    let reported: Error = r#"{"code":"100","message":"false"}"#.into();
+    assert_eq!(error_code_message(&reported), ("100".into(), "false".into()));
+
+    let other: Error = "timeout after 1s".into();
+    assert_eq!(error_code_message(&other), ("".into(), "timeout after 1s".into()));
+
+    let partial: Error = r#"{"code":100,"message":"false"}"#.into();
+    assert_eq!(
+        error_code_message(&partial),
+        ("".into(), r#"{"code":100,"message":"false"}"#.into())
+    );
 }
@@ -25,12 +25,16 @@ pub trait CaseAsset: Sync + Send {
     fn data(&self) -> &Value;
 
     fn state(&self) -> &CaseState;
+
+    /// assets of the `defer` of the steps, in the order they ran
+    fn defer(&self) -> &[Box<dyn StepAsset>];
 }
 
 pub enum CaseState {
     Ok(TailDropVec<Box<dyn StepAsset>>),
     Err(Box<Error>),
     Fail(TailDropVec<Box<dyn StepAsset>>),
+    Cancelled(String),
 }
 
 impl CaseState {
@@ -40,4 +44,11 @@ impl CaseState {
             _ => false,
         }
     }
+
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            CaseState::Cancelled(_) => true,
+            _ => false,
+        }
+    }
 }
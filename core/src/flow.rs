@@ -1,5 +1,6 @@
 use std::borrow::Borrow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::path::Path;
 use std::time::Duration;
 
@@ -8,12 +9,63 @@ use regex::Regex;
 
 use crate::flow::Error::EntryLost;
 use crate::flow::Error::*;
-use crate::value::{Map, Value};
+use crate::value::{json, Map, Value};
 
 lazy_static! {
     pub static ref ID_PATTERN: Regex = Regex::new(r"^[\w]{1,50}$").unwrap();
 }
 
+pub static GOTO_END: &str = "__end__";
+
+/// version of the flow model, older flows are upgraded to it when loaded
+pub static VERSION: &str = "0.0.2";
+
+static ROOT_KEYS: &[&str] = &["version", "def", "stage", "pre", "post"];
+
+static PRE_KEYS: &[&str] = &["step"];
+
+static POST_KEYS: &[&str] = &["step"];
+
+static STAGE_KEYS: &[&str] = &[
+    "step",
+    "loader",
+    "concurrency",
+    "ramp",
+    "arrival",
+    "abort",
+    "round",
+    "duration",
+    "break_on",
+    "case_timeout",
+    "depends_on",
+    "def",
+    "pre",
+    "post",
+];
+
+static RAMP_KEYS: &[&str] = &["to", "duration"];
+
+static ARRIVAL_KEYS: &[&str] = &["rate", "max_in_flight"];
+
+static ABORT_KEYS: &[&str] = &["error_ratio", "window", "consecutive", "scope"];
+
+static STEP_OPTION_KEYS: &[&str] = &["then", "retry", "timeout"];
+
+static STEP_KEYS: &[&str] = &["if", "action", "then", "retry", "timeout", "defer"];
+
+static THEN_KEYS: &[&str] = &["cond", "reg", "goto"];
+
+/// names bound in the context of a case, a `reg` of `then` must not replace them
+static CASE_CONTEXT_KEYS: &[&str] = &[
+    "__meta__", "def", "case", "pre", "step", "stage", "task", "retry",
+];
+
+static RETRY_KEYS: &[&str] = &["max", "delay", "backoff", "cond"];
+
+static ACTION_OPTION_KEYS: &[&str] = &["timeout", "expect_error"];
+
+static EXPECT_ERROR_KEYS: &[&str] = &["code", "message"];
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("invalid id `{0}`")]
@@ -55,80 +107,492 @@ impl Then {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Retry {
+    max: usize,
+    delay: Duration,
+    backoff: String,
+    cond: Option<String>,
+}
+
+impl Retry {
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn cond(&self) -> Option<&str> {
+        self.cond.as_deref()
+    }
+
+    pub fn delay(&self, attempt: usize) -> Duration {
+        match self.backoff.as_str() {
+            "exponential" => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
+                self.delay.saturating_mul(factor)
+            }
+            _ => self.delay,
+        }
+    }
+}
+
+/// patterns the error of an action must match, the action fails if it does not fail
+#[derive(Debug, Clone)]
+pub struct ExpectError {
+    code: Option<Regex>,
+    message: Option<Regex>,
+}
+
+impl ExpectError {
+    pub fn is_match(&self, code: &str, message: &str) -> bool {
+        self.code.as_ref().map(|c| c.is_match(code)).unwrap_or(true)
+            && self.message.as_ref().map(|m| m.is_match(message)).unwrap_or(true)
+    }
+}
+
+impl Display for ExpectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut pattern_vec = vec![];
+        if let Some(code) = self.code.as_ref() {
+            pattern_vec.push(format!("code `{}`", code));
+        }
+        if let Some(message) = self.message.as_ref() {
+            pattern_vec.push(format!("message `{}`", message));
+        }
+        f.write_str(pattern_vec.join(", ").as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ramp {
+    to: usize,
+    duration: Duration,
+}
+
+impl Ramp {
+    pub fn to(&self) -> usize {
+        self.to
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Arrival {
+    rate: usize,
+    max_in_flight: usize,
+}
+
+impl Arrival {
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortScope {
+    Stage,
+    Task,
+}
+
+#[derive(Debug, Clone)]
+pub struct Abort {
+    error_ratio: Option<f64>,
+    window: usize,
+    consecutive: Option<usize>,
+    scope: AbortScope,
+}
+
+impl Abort {
+    /// abort when failed cases in the last `window` cases exceed this ratio
+    pub fn error_ratio(&self) -> Option<f64> {
+        self.error_ratio
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// abort after this many failed cases in a row
+    pub fn consecutive(&self) -> Option<usize> {
+        self.consecutive
+    }
+
+    pub fn scope(&self) -> AbortScope {
+        self.scope
+    }
+}
+
 impl Flow {
     pub fn new(flow: Value, dir: &Path) -> Result<Flow, Error> {
+        let flow = Flow::_new(flow, dir)?;
+        match flow._check().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(flow),
+        }
+    }
+
+    /// every structural error of the flow in document order, `new` fails with the first of them
+    pub fn check(flow: Value, dir: &Path) -> Vec<Error> {
+        match Flow::_new(flow, dir) {
+            Ok(flow) => flow._check(),
+            Err(e) => vec![e],
+        }
+    }
+
+    /// json schema of a flow file, the keys allowed are the ones `new` accepts,
+    /// `action_schema` maps each enabled action to the schema of its args
+    pub fn schema(action_schema: &Map) -> Value {
+        let duration = json!({ "type": "integer", "minimum": 1 });
+        let positive = json!({ "type": "integer", "minimum": 1 });
+
+        let expect_error = object_schema(
+            EXPECT_ERROR_KEYS,
+            json!({
+                "code": { "type": "string" },
+                "message": { "type": "string" },
+            }),
+        );
+        let mut action_property = object_schema(
+            ACTION_OPTION_KEYS,
+            json!({
+                "timeout": duration,
+                "expect_error": { "oneOf": [{ "type": "string" }, expect_error] },
+            }),
+        )["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let mut action_one_of = vec![];
+        for (func, schema) in action_schema {
+            action_property.insert(func.clone(), schema.clone());
+            action_one_of.push(json!({ "required": [func] }));
+        }
+        let action = json!({
+            "type": "object",
+            "properties": action_property,
+            "propertyNames": { "enum": action_property.keys().collect::<Vec<&String>>() },
+            "oneOf": action_one_of,
+        });
+
+        let then = object_schema(
+            THEN_KEYS,
+            json!({
+                "cond": { "type": "string" },
+                "reg": { "type": "object" },
+                "goto": { "type": "string" },
+            }),
+        );
+        let retry = object_schema(
+            RETRY_KEYS,
+            json!({
+                "max": positive,
+                "delay": { "type": "integer", "minimum": 0 },
+                "backoff": { "enum": ["fixed", "exponential"] },
+                "cond": { "type": "string" },
+            }),
+        );
+        let mut step = object_schema(
+            STEP_KEYS,
+            json!({
+                "if": { "type": "string" },
+                "action": {
+                    "type": "object",
+                    "minProperties": 1,
+                    "propertyNames": { "pattern": ID_PATTERN.as_str() },
+                    "additionalProperties": { "$ref": "#/$defs/action" },
+                },
+                "then": { "type": "array", "items": then },
+                "retry": retry,
+                "timeout": duration,
+                "defer": {
+                    "type": "object",
+                    "propertyNames": { "pattern": ID_PATTERN.as_str() },
+                    "additionalProperties": { "$ref": "#/$defs/action" },
+                },
+            }),
+        );
+        step["required"] = json!(["action"]);
+        let step_map = json!({
+            "type": "object",
+            "minProperties": 1,
+            "propertyNames": { "pattern": ID_PATTERN.as_str() },
+            "additionalProperties": { "$ref": "#/$defs/step" },
+        });
+
+        let ramp = object_schema(
+            RAMP_KEYS,
+            json!({
+                "to": { "type": "integer", "minimum": 0 },
+                "duration": { "type": "integer", "minimum": 0 },
+            }),
+        );
+        let arrival = object_schema(
+            ARRIVAL_KEYS,
+            json!({
+                "rate": positive,
+                "max_in_flight": positive,
+            }),
+        );
+        let abort = object_schema(
+            ABORT_KEYS,
+            json!({
+                "error_ratio": { "type": "number", "minimum": 0, "exclusiveMaximum": 1 },
+                "window": positive,
+                "consecutive": positive,
+                "scope": { "enum": ["stage", "task"] },
+            }),
+        );
+        let break_on = json!({ "enum": ["never", "stage_fail", "stage_timeout"] });
+        let mut stage = object_schema(
+            STAGE_KEYS,
+            json!({
+                "step": step_map,
+                "loader": { "type": "object" },
+                "concurrency": positive,
+                "ramp": { "type": "array", "minItems": 1, "items": ramp },
+                "arrival": arrival,
+                "abort": abort,
+                "round": positive,
+                "duration": duration,
+                "break_on": {
+                    "anyOf": [break_on, { "type": "array", "items": break_on }]
+                },
+                "case_timeout": duration,
+                "depends_on": { "type": "array", "items": { "type": "string" } },
+                "def": { "type": "object" },
+                "pre": object_schema(PRE_KEYS, json!({ "step": step_map })),
+                "post": object_schema(POST_KEYS, json!({ "step": step_map })),
+            }),
+        );
+        stage["required"] = json!(["step"]);
+
+        let pre = object_schema(PRE_KEYS, json!({ "step": step_map }));
+        let post = object_schema(POST_KEYS, json!({ "step": step_map }));
+        let root = object_schema(
+            ROOT_KEYS,
+            json!({
+                "version": { "const": VERSION },
+                "def": { "type": "object" },
+                "stage": {
+                    "type": "object",
+                    "minProperties": 1,
+                    "propertyNames": { "pattern": ID_PATTERN.as_str() },
+                    "additionalProperties": stage,
+                },
+                "pre": pre,
+                "post": post,
+            }),
+        );
+
+        let mut schema = Map::new();
+        schema.insert(
+            "$schema".into(),
+            json!("https://json-schema.org/draft/2020-12/schema"),
+        );
+        schema.insert("title".into(), json!("chord task"));
+        if let Value::Object(root) = root {
+            schema.extend(root);
+        }
+        schema.insert("required".into(), json!(["version", "stage"]));
+        schema.insert(
+            "$defs".into(),
+            json!({
+                "step": step,
+                "action": action,
+            }),
+        );
+        Value::Object(schema)
+    }
+
+    /// the flow in the current version, flows in other versions are returned as they are
+    ///
+    /// 0.0.1 puts the actions of a step beside its options,
+    /// 0.0.2 moves them into the `action` entry of the step.
+    /// an action of 0.0.1 named after a step option can not be told apart from it, so it fails
+    pub fn upgrade(mut flow: Value) -> Result<Value, Error> {
+        if flow["version"].as_str() != Some("0.0.1") {
+            return Ok(flow);
+        }
+
+        if let Some(step_map) = flow.get_mut("pre").and_then(|p| p.get_mut("step")) {
+            step_map_upgrade(step_map)?;
+        }
+        if let Some(step_map) = flow.get_mut("post").and_then(|p| p.get_mut("step")) {
+            step_map_upgrade(step_map)?;
+        }
+        if let Some(Value::Object(stage_map)) = flow.get_mut("stage") {
+            for stage in stage_map.values_mut() {
+                if let Some(step_map) = stage.get_mut("step") {
+                    step_map_upgrade(step_map)?;
+                }
+                for hook in ["pre", "post"] {
+                    if let Some(step_map) = stage.get_mut(hook).and_then(|h| h.get_mut("step")) {
+                        step_map_upgrade(step_map)?;
+                    }
+                }
+            }
+        }
+        flow["version"] = Value::String("0.0.2".into());
+        Ok(flow)
+    }
+
+    fn _new(flow: Value, dir: &Path) -> Result<Flow, Error> {
+        let flow = Flow::upgrade(flow)?;
         let mut meta = Map::new();
         meta.insert(
             "task_dir".to_string(),
             Value::String(dir.to_path_buf().to_str().unwrap().to_string()),
         );
+        Ok(Flow { flow, meta })
+    }
 
-        let flow = Flow { flow, meta };
-
-        flow._root_check()?;
-        flow._version()?;
+    fn _check(&self) -> Vec<Error> {
+        let mut err_vec = ErrorVec::default();
+        if !err_vec.check(self._root_check()) {
+            return err_vec.into();
+        }
+        err_vec.check(self._version());
 
-        let mut step_id_checked: HashSet<&str> = HashSet::new();
-        let pre_step_id_vec = flow.pre_step_id_vec().unwrap_or(vec![]);
-        for pre_step_id in pre_step_id_vec {
-            if !ID_PATTERN.is_match(pre_step_id) {
-                return Err(IdInvalid(pre_step_id.into()));
-            }
-            if step_id_checked.contains(pre_step_id) {
-                return Err(IdDuplicated(pre_step_id.into()));
-            } else {
-                step_id_checked.insert(pre_step_id.into());
+        let mut step_id_checked: Vec<&str> = Vec::new();
+        let mut pre_step_id_vec = vec![];
+        if !self.flow["pre"].is_null() && err_vec.check(self._pre_check()) {
+            pre_step_id_vec = self.pre_step_id_vec().unwrap_or_default();
+            for pre_step_id in pre_step_id_vec.iter() {
+                self._step_id_check(pre_step_id, &mut step_id_checked, &mut err_vec);
             }
+        }
 
-            flow._pre_check()?;
+        let mut post_step_id_vec = vec![];
+        if !self.flow["post"].is_null() && err_vec.check(self._post_check()) {
+            post_step_id_vec = self.post_step_id_vec().unwrap_or_default();
+            for post_step_id in post_step_id_vec.iter() {
+                self._step_id_check(post_step_id, &mut step_id_checked, &mut err_vec);
+            }
         }
 
-        let stage_id_vec = flow._stage_id_vec()?;
+        let stage_id_vec = match self._stage_id_vec() {
+            Ok(stage_id_vec) => stage_id_vec,
+            Err(e) => {
+                err_vec.push(e);
+                return err_vec.into();
+            }
+        };
         if stage_id_vec.is_empty() {
-            return Err(EntryLost("root".into(), "stage".into()));
+            err_vec.push(EntryLost("root".into(), "stage".into()));
         }
 
         for stage_id in stage_id_vec {
             if !ID_PATTERN.is_match(stage_id) {
-                return Err(IdInvalid(stage_id.into()));
+                err_vec.push(IdInvalid(stage_id.into()));
+            }
+            if !self.flow["stage"][stage_id].is_object() {
+                err_vec.check(self._stage_check(stage_id));
+                continue;
             }
 
-            flow._stage_check(stage_id)?;
-            flow._stage_concurrency(stage_id)?;
-            flow._stage_duration(stage_id)?;
-            flow._stage_round(stage_id)?;
-            flow._stage_break_on(stage_id)?;
+            err_vec.check(self._stage_check(stage_id));
+            err_vec.check(self._stage_ramp(stage_id));
+            err_vec.check(self._stage_arrival(stage_id));
+            err_vec.check(self._stage_concurrency(stage_id));
+            err_vec.check(self._stage_duration(stage_id));
+            err_vec.check(self._stage_round(stage_id));
+            err_vec.check(self._stage_break_on(stage_id));
+            err_vec.check(self._stage_abort(stage_id));
+            err_vec.check(self._stage_case_timeout(stage_id));
+            err_vec.check(self._stage_depends_on(stage_id));
+            err_vec.check(self._stage_def(stage_id));
+            for hook in ["pre", "post"] {
+                if self.flow["stage"][stage_id][hook].is_null()
+                    || !err_vec.check(self._stage_hook_check(stage_id, hook))
+                {
+                    continue;
+                }
+                let hook_step_id_vec = self._stage_hook_step_id_vec(stage_id, hook);
+                for hook_step_id in hook_step_id_vec.iter() {
+                    self._step_id_check(hook_step_id, &mut step_id_checked, &mut err_vec);
+                }
+                self._step_goto_check(&hook_step_id_vec, &mut err_vec);
+            }
 
-            let stage_step_id_vec = flow._stage_step_id_vec(stage_id)?;
+            let stage_step_id_vec = match self._stage_step_id_vec(stage_id) {
+                Ok(stage_step_id_vec) => stage_step_id_vec,
+                Err(e) => {
+                    err_vec.push(e);
+                    continue;
+                }
+            };
+            for stage_step_id in stage_step_id_vec.iter() {
+                self._step_id_check(stage_step_id, &mut step_id_checked, &mut err_vec);
+            }
+            self._step_goto_check(&stage_step_id_vec, &mut err_vec);
+        }
+        self._step_goto_check(&pre_step_id_vec, &mut err_vec);
+        self._step_goto_check(&post_step_id_vec, &mut err_vec);
+        self._stage_cycle_check(&mut err_vec);
 
-            if stage_step_id_vec.is_empty() {
-                return Err(EntryLost(stage_id.into(), "step".into()));
+        for step_id in step_id_checked {
+            if !err_vec.check(self._step_check(step_id)) {
+                continue;
             }
+            err_vec.check(self._step_if(step_id));
+            err_vec.check(self._step_then(step_id));
+            err_vec.check(self._step_retry(step_id));
+            err_vec.check(self._step_timeout(step_id));
 
-            for stage_step_id in stage_step_id_vec {
-                if !ID_PATTERN.is_match(stage_step_id) {
-                    return Err(IdInvalid(stage_step_id.into()));
+            let aid_vec = match self._step_action_id_vec(step_id) {
+                Ok(aid_vec) => aid_vec,
+                Err(e) => {
+                    err_vec.push(e);
+                    continue;
                 }
+            };
+            for aid in aid_vec {
+                if !err_vec.check(self._step_action_obj(step_id, aid)) {
+                    continue;
+                }
+                err_vec.check(self._step_action_timeout(step_id, aid));
+                err_vec.check(self._step_entry_expect_error(step_id, "action", aid));
+            }
 
-                if step_id_checked.contains(stage_step_id) {
-                    return Err(IdDuplicated(stage_step_id.into()));
-                } else {
-                    step_id_checked.insert(stage_step_id.into());
+            let defer_id_vec = match self._step_defer_id_vec(step_id) {
+                Ok(defer_id_vec) => defer_id_vec,
+                Err(e) => {
+                    err_vec.push(e);
+                    continue;
+                }
+            };
+            for did in defer_id_vec {
+                if !err_vec.check(self._step_entry_obj(step_id, "defer", did)) {
+                    continue;
                 }
+                err_vec.check(self._step_entry_timeout(step_id, "defer", did));
+                err_vec.check(self._step_entry_expect_error(step_id, "defer", did));
             }
         }
 
-        for step_id in step_id_checked.iter() {
-            flow._step_check(step_id)?;
+        err_vec.into()
+    }
 
-            for (aid, _) in flow._step_obj(step_id)? {
-                flow._step_action_obj(step_id, aid)?;
-                flow._step_action_func(step_id, aid)?;
-                flow._step_action_args(step_id, aid)?;
-            }
+    fn _step_id_check<'s>(
+        &self,
+        step_id: &'s str,
+        step_id_checked: &mut Vec<&'s str>,
+        err_vec: &mut ErrorVec,
+    ) {
+        if !ID_PATTERN.is_match(step_id) {
+            err_vec.push(IdInvalid(step_id.into()));
+        }
+        if step_id_checked.contains(&step_id) {
+            err_vec.push(IdDuplicated(step_id.into()));
+        } else {
+            step_id_checked.push(step_id);
         }
-
-        return Ok(flow);
     }
 
     pub fn version(&self) -> &str {
@@ -147,6 +611,21 @@ impl Flow {
         self._stage_step_id_vec(stage_id).unwrap()
     }
 
+    /// variables of the stage, rendered at the start of each round
+    pub fn stage_def(&self, stage_id: &str) -> Option<&Map> {
+        self._stage_def(stage_id).unwrap()
+    }
+
+    /// steps run once at the start of each round of the stage
+    pub fn stage_pre_step_id_vec(&self, stage_id: &str) -> Option<Vec<&str>> {
+        Some(self._stage_hook_step_id_vec(stage_id, "pre")).filter(|v| !v.is_empty())
+    }
+
+    /// steps run once at the end of each round of the stage, whatever its cases ended in
+    pub fn stage_post_step_id_vec(&self, stage_id: &str) -> Option<Vec<&str>> {
+        Some(self._stage_hook_step_id_vec(stage_id, "post")).filter(|v| !v.is_empty())
+    }
+
     pub fn pre_step_id_vec(&self) -> Option<Vec<&str>> {
         let task_step_chain_arr = self.flow["pre"]["step"]
             .as_object()
@@ -162,6 +641,18 @@ impl Flow {
         };
     }
 
+    /// steps run once after all stages, whatever the stages ended in
+    pub fn post_step_id_vec(&self) -> Option<Vec<&str>> {
+        let id_vec: Vec<&str> = self.flow["post"]["step"]
+            .as_object()
+            .map(|p| p.keys().map(|k| k.as_str()).collect())?;
+        if id_vec.is_empty() {
+            None
+        } else {
+            Some(id_vec)
+        }
+    }
+
     pub fn stage_id_vec(&self) -> Vec<&str> {
         self._stage_id_vec().unwrap()
     }
@@ -177,6 +668,18 @@ impl Flow {
         self._stage_concurrency(stage_id).unwrap()
     }
 
+    pub fn stage_ramp(&self, stage_id: &str) -> Option<Vec<Ramp>> {
+        self._stage_ramp(stage_id).unwrap()
+    }
+
+    pub fn stage_arrival(&self, stage_id: &str) -> Option<Arrival> {
+        self._stage_arrival(stage_id).unwrap()
+    }
+
+    pub fn stage_abort(&self, stage_id: &str) -> Option<Abort> {
+        self._stage_abort(stage_id).unwrap()
+    }
+
     pub fn stage_round(&self, stage_id: &str) -> usize {
         self._stage_round(stage_id).unwrap()
     }
@@ -185,14 +688,53 @@ impl Flow {
         self._stage_duration(stage_id).unwrap()
     }
 
-    pub fn stage_break_on(&self, stage_id: &str) -> &str {
+    pub fn stage_break_on(&self, stage_id: &str) -> Vec<&str> {
         self._stage_break_on(stage_id).unwrap()
     }
 
+    pub fn stage_case_timeout(&self, stage_id: &str) -> Option<Duration> {
+        self._stage_case_timeout(stage_id).unwrap()
+    }
+
+    /// stages which must end before the stage starts,
+    /// the stage before it if no stage of the flow uses `depends_on`
+    pub fn stage_depends_on(&self, stage_id: &str) -> Vec<&str> {
+        self._stage_depends_on(stage_id).unwrap()
+    }
+
     pub fn step_obj(&self, step_id: &str) -> &Map {
         self._step_obj(step_id).unwrap()
     }
 
+    pub fn step_action_id_vec(&self, step_id: &str) -> Vec<&str> {
+        self._step_action_id_vec(step_id).unwrap()
+    }
+
+    /// the step is skipped unless it renders to `true`
+    pub fn step_if(&self, step_id: &str) -> Option<String> {
+        self._step_if(step_id).unwrap()
+    }
+
+    pub fn step_then(&self, step_id: &str) -> Option<Vec<Then>> {
+        self._step_then(step_id).unwrap()
+    }
+
+    pub fn step_retry(&self, step_id: &str) -> Option<Retry> {
+        self._step_retry(step_id).unwrap()
+    }
+
+    pub fn step_timeout(&self, step_id: &str) -> Option<Duration> {
+        self._step_timeout(step_id).unwrap()
+    }
+
+    pub fn step_action_timeout(&self, step_id: &str, action_id: &str) -> Option<Duration> {
+        self._step_action_timeout(step_id, action_id).unwrap()
+    }
+
+    pub fn step_action_expect_error(&self, step_id: &str, action_id: &str) -> Option<ExpectError> {
+        self._step_entry_expect_error(step_id, "action", action_id).unwrap()
+    }
+
     pub fn step_action_func(&self, step_id: &str, action_id: &str) -> &str {
         self._step_action_func(step_id, action_id).unwrap()
     }
@@ -201,11 +743,32 @@ impl Flow {
         self._step_action_args(step_id, action_id).unwrap()
     }
 
+    /// actions registered once the step is ok, run at the end of the case whatever its outcome
+    pub fn step_defer_id_vec(&self, step_id: &str) -> Vec<&str> {
+        self._step_defer_id_vec(step_id).unwrap()
+    }
+
+    pub fn step_defer_timeout(&self, step_id: &str, defer_id: &str) -> Option<Duration> {
+        self._step_entry_timeout(step_id, "defer", defer_id).unwrap()
+    }
+
+    pub fn step_defer_expect_error(&self, step_id: &str, defer_id: &str) -> Option<ExpectError> {
+        self._step_entry_expect_error(step_id, "defer", defer_id).unwrap()
+    }
+
+    pub fn step_defer_func(&self, step_id: &str, defer_id: &str) -> &str {
+        self._step_entry_only(step_id, "defer", defer_id).unwrap().0.as_str()
+    }
+
+    pub fn step_defer_args(&self, step_id: &str, defer_id: &str) -> &Value {
+        self._step_entry_only(step_id, "defer", defer_id).unwrap().1
+    }
+
     // -----------------------------------------------
     // private
 
     fn _root_check(&self) -> Result<(), Error> {
-        let enable_keys = vec!["version", "def", "stage", "pre"];
+        let enable_keys = ROOT_KEYS;
         let root = self.flow.borrow();
         let object = root
             .as_object()
@@ -223,15 +786,19 @@ impl Flow {
             .as_str()
             .ok_or(EntryLost("root".into(), "version".into()))?;
 
-        if v != "0.0.1" {
-            return Err(Violation("version".into(), "0.0.1".into(), v.into()));
+        if v != VERSION {
+            return Err(Violation(
+                "version".into(),
+                format!("be 0.0.1 or {}", VERSION),
+                format!("is {}", v),
+            ));
         } else {
             Ok(v)
         }
     }
 
     fn _pre_check(&self) -> Result<(), Error> {
-        let enable_keys = vec!["step"];
+        let enable_keys = PRE_KEYS;
         let pre = self.flow["pre"].borrow();
         let object = pre
             .as_object()
@@ -244,6 +811,20 @@ impl Flow {
         return Ok(());
     }
 
+    fn _post_check(&self) -> Result<(), Error> {
+        let enable_keys = POST_KEYS;
+        let post = self.flow["post"].borrow();
+        let object = post
+            .as_object()
+            .ok_or_else(|| Violation("post".into(), "be a object".into(), "is not".into()))?;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected("post".into(), k.into()));
+            }
+        }
+        Ok(())
+    }
+
     fn _stage_id_vec(&self) -> Result<Vec<&str>, Error> {
         let step_id_vec = self.flow["stage"]
             .as_object()
@@ -253,14 +834,7 @@ impl Flow {
     }
 
     fn _stage_check(&self, stage_id: &str) -> Result<(), Error> {
-        let enable_keys = vec![
-            "step",
-            "loader",
-            "concurrency",
-            "round",
-            "duration",
-            "break_on",
-        ];
+        let enable_keys = STAGE_KEYS;
         let stage = self.flow["stage"][stage_id].borrow();
         let object = stage.as_object().ok_or_else(|| {
             Violation(
@@ -278,6 +852,13 @@ impl Flow {
     }
 
     fn _stage_concurrency(&self, stage_id: &str) -> Result<usize, Error> {
+        if let Some(ramp_vec) = self._stage_ramp(stage_id)? {
+            return Ok(ramp_vec.iter().map(|r| r.to).max().unwrap_or(1));
+        }
+        if let Some(arrival) = self._stage_arrival(stage_id)? {
+            return Ok(arrival.max_in_flight);
+        }
+
         let s = self.flow["stage"][stage_id]["concurrency"].as_u64();
         if s.is_none() {
             return Ok(10);
@@ -294,6 +875,112 @@ impl Flow {
         Ok(s as usize)
     }
 
+    fn _stage_ramp(&self, stage_id: &str) -> Result<Option<Vec<Ramp>>, Error> {
+        let ramp = self.flow["stage"][stage_id]["ramp"].borrow();
+        if ramp.is_null() {
+            return Ok(None);
+        }
+
+        let path = format!("stage.{}.ramp", stage_id);
+        if !self.flow["stage"][stage_id]["concurrency"].is_null() {
+            return Err(Violation(
+                path,
+                "not be used with concurrency".into(),
+                "is".into(),
+            ));
+        }
+
+        let ramp_arr = ramp
+            .as_array()
+            .filter(|r| !r.is_empty())
+            .ok_or_else(|| Violation(path.clone(), "be a non-empty array".into(), "is not".into()))?;
+
+        let enable_keys = RAMP_KEYS;
+        let mut ramp_vec = Vec::with_capacity(ramp_arr.len());
+        for (idx, ramp) in ramp_arr.iter().enumerate() {
+            let path = format!("{}.{}", path, idx);
+            let object = ramp
+                .as_object()
+                .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+            for (k, _) in object {
+                if !enable_keys.contains(&k.as_str()) {
+                    return Err(EntryUnexpected(path, k.into()));
+                }
+            }
+
+            let to = ramp["to"].as_u64().ok_or_else(|| {
+                Violation(format!("{}.to", path), ">= 0".into(), format!("is {}", ramp["to"]))
+            })?;
+            let duration = ramp["duration"].as_u64().ok_or_else(|| {
+                Violation(
+                    format!("{}.duration", path),
+                    ">= 0".into(),
+                    format!("is {}", ramp["duration"]),
+                )
+            })?;
+            ramp_vec.push(Ramp {
+                to: to as usize,
+                duration: Duration::from_secs(duration),
+            });
+        }
+
+        if ramp_vec.iter().all(|r| r.to < 1) {
+            return Err(Violation(
+                format!("{}.to", path),
+                "> 0 at least once".into(),
+                "is not".into(),
+            ));
+        }
+        Ok(Some(ramp_vec))
+    }
+
+    fn _stage_arrival(&self, stage_id: &str) -> Result<Option<Arrival>, Error> {
+        let arrival = self.flow["stage"][stage_id]["arrival"].borrow();
+        if arrival.is_null() {
+            return Ok(None);
+        }
+
+        let path = format!("stage.{}.arrival", stage_id);
+        for key in ["concurrency", "ramp"] {
+            if !self.flow["stage"][stage_id][key].is_null() {
+                return Err(Violation(
+                    path,
+                    format!("not be used with {}", key),
+                    "is".into(),
+                ));
+            }
+        }
+
+        let object = arrival
+            .as_object()
+            .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+        let enable_keys = ARRIVAL_KEYS;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected(path, k.into()));
+            }
+        }
+
+        let rate = arrival["rate"]
+            .as_u64()
+            .filter(|r| *r > 0)
+            .ok_or_else(|| {
+                Violation(format!("{}.rate", path), "> 0".into(), format!("is {}", arrival["rate"]))
+            })? as usize;
+
+        let max_in_flight = match arrival["max_in_flight"].borrow() {
+            Value::Null => rate,
+            v => v.as_u64().filter(|m| *m > 0).ok_or_else(|| {
+                Violation(format!("{}.max_in_flight", path), "> 0".into(), format!("is {}", v))
+            })? as usize,
+        };
+
+        Ok(Some(Arrival {
+            rate,
+            max_in_flight,
+        }))
+    }
+
     fn _stage_round(&self, stage_id: &str) -> Result<usize, Error> {
         let s = self.flow["stage"][stage_id]["round"].as_u64();
         if s.is_none() {
@@ -328,18 +1015,224 @@ impl Flow {
         Ok(Duration::from_secs(s))
     }
 
-    fn _stage_break_on(&self, stage_id: &str) -> Result<&str, Error> {
-        let break_on = self.flow["stage"][stage_id]["break_on"]
-            .as_str()
-            .unwrap_or("stage_fail");
-        match break_on {
-            "never" => Ok(break_on),
-            "stage_fail" => Ok(break_on),
-            o => Err(ValueUnexpected(
-                format!("stage.{}.break_on", stage_id),
-                o.into(),
-            )),
+    fn _stage_break_on(&self, stage_id: &str) -> Result<Vec<&str>, Error> {
+        let break_on = self.flow["stage"][stage_id]["break_on"].borrow();
+        let break_on_vec = match break_on {
+            Value::Null => vec!["stage_fail"],
+            Value::String(b) => vec![b.as_str()],
+            Value::Array(arr) => arr
+                .iter()
+                .map(|b| {
+                    b.as_str().ok_or_else(|| {
+                        Violation(
+                            format!("stage.{}.break_on", stage_id),
+                            "be a string".into(),
+                            "is not".into(),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<&str>, Error>>()?,
+            _ => {
+                return Err(Violation(
+                    format!("stage.{}.break_on", stage_id),
+                    "be a string or array".into(),
+                    "is not".into(),
+                ));
+            }
+        };
+
+        for b in break_on_vec.iter() {
+            match *b {
+                "never" => (),
+                "stage_fail" => (),
+                "stage_timeout" => (),
+                o => {
+                    return Err(ValueUnexpected(
+                        format!("stage.{}.break_on", stage_id),
+                        o.into(),
+                    ));
+                }
+            }
         }
+        Ok(break_on_vec)
+    }
+
+    fn _stage_abort(&self, stage_id: &str) -> Result<Option<Abort>, Error> {
+        let abort = self.flow["stage"][stage_id]["abort"].borrow();
+        if abort.is_null() {
+            return Ok(None);
+        }
+
+        let path = format!("stage.{}.abort", stage_id);
+        let object = abort
+            .as_object()
+            .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+        let enable_keys = ABORT_KEYS;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected(path, k.into()));
+            }
+        }
+
+        let error_ratio = match abort["error_ratio"].borrow() {
+            Value::Null => None,
+            v => Some(v.as_f64().filter(|r| *r >= 0.0 && *r < 1.0).ok_or_else(|| {
+                Violation(
+                    format!("{}.error_ratio", path),
+                    ">= 0 and < 1".into(),
+                    format!("is {}", v),
+                )
+            })?),
+        };
+
+        let window = match abort["window"].borrow() {
+            Value::Null => 100,
+            v => v.as_u64().filter(|w| *w > 0).ok_or_else(|| {
+                Violation(format!("{}.window", path), "> 0".into(), format!("is {}", v))
+            })? as usize,
+        };
+
+        let consecutive = match abort["consecutive"].borrow() {
+            Value::Null => None,
+            v => Some(v.as_u64().filter(|c| *c > 0).ok_or_else(|| {
+                Violation(format!("{}.consecutive", path), "> 0".into(), format!("is {}", v))
+            })? as usize),
+        };
+
+        if error_ratio.is_none() && consecutive.is_none() {
+            return Err(EntryLost(path, "error_ratio".into()));
+        }
+
+        let scope = match abort["scope"].borrow() {
+            Value::Null => AbortScope::Stage,
+            Value::String(s) if s == "stage" => AbortScope::Stage,
+            Value::String(s) if s == "task" => AbortScope::Task,
+            v => {
+                return Err(ValueUnexpected(format!("{}.scope", path), v.to_string()));
+            }
+        };
+
+        Ok(Some(Abort {
+            error_ratio,
+            window,
+            consecutive,
+            scope,
+        }))
+    }
+
+    fn _stage_case_timeout(&self, stage_id: &str) -> Result<Option<Duration>, Error> {
+        self._timeout(
+            format!("stage.{}.case_timeout", stage_id),
+            &self.flow["stage"][stage_id]["case_timeout"],
+        )
+    }
+
+    fn _stage_def(&self, stage_id: &str) -> Result<Option<&Map>, Error> {
+        let def = &self.flow["stage"][stage_id]["def"];
+        if def.is_null() {
+            return Ok(None);
+        }
+        def.as_object().map(Some).ok_or_else(|| {
+            Violation(
+                format!("stage.{}.def", stage_id),
+                "be a object".into(),
+                "is not".into(),
+            )
+        })
+    }
+
+    fn _stage_hook_check(&self, stage_id: &str, hook: &str) -> Result<(), Error> {
+        let enable_keys = if hook == "pre" { PRE_KEYS } else { POST_KEYS };
+        let path = format!("stage.{}.{}", stage_id, hook);
+        let object = self.flow["stage"][stage_id][hook]
+            .as_object()
+            .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected(path, k.into()));
+            }
+        }
+        Ok(())
+    }
+
+    fn _stage_hook_step_id_vec(&self, stage_id: &str, hook: &str) -> Vec<&str> {
+        self.flow["stage"][stage_id][hook]["step"]
+            .as_object()
+            .map(|p| p.keys().map(|k| k.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    fn _stage_depends_on(&self, stage_id: &str) -> Result<Vec<&str>, Error> {
+        let stage_id_vec = self._stage_id_vec()?;
+        let path = format!("stage.{}.depends_on", stage_id);
+        let depends_on_vec = match self.flow["stage"][stage_id]["depends_on"].borrow() {
+            // stages run one after another unless one of them uses `depends_on`,
+            // then a stage without it depends on nothing
+            Value::Null => {
+                let graph = stage_id_vec
+                    .iter()
+                    .any(|s| !self.flow["stage"][s]["depends_on"].is_null());
+                if graph {
+                    return Ok(vec![]);
+                }
+                let idx = stage_id_vec.iter().position(|s| *s == stage_id).unwrap_or(0);
+                return Ok(stage_id_vec[..idx].last().into_iter().copied().collect());
+            }
+            Value::Array(arr) => arr
+                .iter()
+                .map(|d| {
+                    d.as_str().ok_or_else(|| {
+                        Violation(path.clone(), "be a string array".into(), "is not".into())
+                    })
+                })
+                .collect::<Result<Vec<&str>, Error>>()?,
+            _ => {
+                return Err(Violation(path, "be a array".into(), "is not".into()));
+            }
+        };
+
+        for depends_on in depends_on_vec.iter() {
+            if *depends_on == stage_id || !stage_id_vec.contains(depends_on) {
+                return Err(ValueUnexpected(path, depends_on.to_string()));
+            }
+        }
+        Ok(depends_on_vec)
+    }
+
+    fn _stage_cycle_check(&self, err_vec: &mut ErrorVec) {
+        let stage_id_vec = self._stage_id_vec().unwrap_or_default();
+        let depends_on_map: HashMap<&str, Vec<&str>> = stage_id_vec
+            .iter()
+            .map(|s| (*s, self._stage_depends_on(s).unwrap_or_default()))
+            .collect();
+        let mut visited = HashSet::new();
+        for stage_id in stage_id_vec {
+            let mut stack = vec![];
+            if let Some(cycle) =
+                stage_cycle_find(stage_id, &depends_on_map, &mut visited, &mut stack)
+            {
+                err_vec.push(Violation(
+                    format!("stage.{}.depends_on", cycle[cycle.len() - 2]),
+                    "not be cyclic".into(),
+                    format!("is {}", cycle.join(" -> ")),
+                ));
+                return;
+            }
+        }
+    }
+
+    fn _timeout(&self, path: String, timeout: &Value) -> Result<Option<Duration>, Error> {
+        if timeout.is_null() {
+            return Ok(None);
+        }
+
+        let s = timeout
+            .as_u64()
+            .ok_or_else(|| Violation(path.clone(), "be a number".into(), "is not".into()))?;
+        if s < 1 {
+            return Err(Violation(path, "> 0".into(), format!("is {}", s)));
+        }
+        Ok(Some(Duration::from_secs(s)))
     }
 
     fn _stage_step_id_vec(&self, stage_id: &str) -> Result<Vec<&str>, Error> {
@@ -358,26 +1251,39 @@ impl Flow {
     }
 
     fn _step_check(&self, step_id: &str) -> Result<(), Error> {
+        let enable_keys = STEP_KEYS;
         let step = self._step(step_id);
-        let _ = step.as_object().ok_or_else(|| {
+        let object = step.as_object().ok_or_else(|| {
             Violation(
                 format!("step.{}", step_id),
                 "be a object".into(),
                 "is not".into(),
             )
         })?;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected(format!("step.{}", step_id), k.into()));
+            }
+        }
         return Ok(());
     }
 
     fn _step(&self, step_id: &str) -> &Value {
         for stage_id in self.stage_id_vec() {
-            let step = self.flow["stage"][stage_id]["step"][step_id].borrow();
-            if !step.is_null() {
-                return step;
+            let stage = self.flow["stage"][stage_id].borrow();
+            for step_map in [&stage["step"], &stage["pre"]["step"], &stage["post"]["step"]] {
+                let step = step_map[step_id].borrow();
+                if !step.is_null() {
+                    return step;
+                }
             }
         }
 
-        return self.flow["pre"]["step"][step_id].borrow();
+        let step = self.flow["pre"]["step"][step_id].borrow();
+        if !step.is_null() {
+            return step;
+        }
+        self.flow["post"]["step"][step_id].borrow()
     }
 
     fn _step_obj(&self, step_id: &str) -> Result<&Map, Error> {
@@ -391,32 +1297,727 @@ impl Flow {
         })
     }
 
-    fn _step_action_obj(&self, step_id: &str, action_id: &str) -> Result<&Map, Error> {
-        let obj = self._step(step_id)[action_id].as_object().ok_or(Violation(
-            format!("{}.{}", step_id, action_id),
-            "be a object".into(),
-            "is not".into(),
-        ))?;
-        return if obj.len() != 1 {
-            Err(Violation(
-                format!("{}.{}", step_id, action_id),
-                "have 1 entry".into(),
+    fn _step_action_id_vec(&self, step_id: &str) -> Result<Vec<&str>, Error> {
+        let step_obj = self._step_obj(step_id)?;
+        let action = step_obj
+            .get("action")
+            .ok_or_else(|| EntryLost(format!("step.{}", step_id), "action".into()))?;
+        let aid_vec: Vec<&str> = action
+            .as_object()
+            .map(|a| a.keys().map(|k| k.as_str()).collect())
+            .ok_or_else(|| {
+                Violation(
+                    format!("step.{}.action", step_id),
+                    "be a object".into(),
+                    "is not".into(),
+                )
+            })?;
+        if aid_vec.is_empty() {
+            return Err(Violation(
+                format!("step.{}.action", step_id),
+                "not empty".into(),
+                "is".into(),
+            ));
+        }
+        Ok(aid_vec)
+    }
+
+    fn _step_if(&self, step_id: &str) -> Result<Option<String>, Error> {
+        match &self._step(step_id)["if"] {
+            Value::Null => Ok(None),
+            Value::String(c) => Ok(Some(c.trim().to_string())),
+            _ => Err(Violation(
+                format!("step.{}.if", step_id),
+                "be a string".into(),
+                "is not".into(),
+            )),
+        }
+    }
+
+    fn _step_then(&self, step_id: &str) -> Result<Option<Vec<Then>>, Error> {
+        let then = self._step(step_id)["then"].borrow();
+        if then.is_null() {
+            return Ok(None);
+        }
+
+        let then_arr = then.as_array().ok_or_else(|| {
+            Violation(
+                format!("step.{}.then", step_id),
+                "be a array".into(),
                 "is not".into(),
-            ))
+            )
+        })?;
+
+        let enable_keys = THEN_KEYS;
+        let mut then_vec = Vec::with_capacity(then_arr.len());
+        for (idx, then) in then_arr.iter().enumerate() {
+            let path = format!("step.{}.then.{}", step_id, idx);
+            let object = then
+                .as_object()
+                .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+            for (k, _) in object {
+                if !enable_keys.contains(&k.as_str()) {
+                    return Err(EntryUnexpected(path, k.into()));
+                }
+            }
+
+            let cond = match &then["cond"] {
+                Value::Null => None,
+                Value::String(c) => Some(c.trim().to_string()),
+                _ => {
+                    return Err(Violation(
+                        format!("{}.cond", path),
+                        "be a string".into(),
+                        "is not".into(),
+                    ));
+                }
+            };
+
+            let reg = match &then["reg"] {
+                Value::Null => None,
+                Value::Object(r) => {
+                    if let Some(k) = r.keys().find(|k| CASE_CONTEXT_KEYS.contains(&k.as_str())) {
+                        return Err(Violation(
+                            format!("{}.reg.{}", path, k),
+                            "not be a name of the case context".into(),
+                            "is".into(),
+                        ));
+                    }
+                    Some(r.clone())
+                }
+                _ => {
+                    return Err(Violation(
+                        format!("{}.reg", path),
+                        "be a object".into(),
+                        "is not".into(),
+                    ));
+                }
+            };
+
+            let goto = match &then["goto"] {
+                Value::Null => None,
+                Value::String(g) => Some(g.clone()),
+                _ => {
+                    return Err(Violation(
+                        format!("{}.goto", path),
+                        "be a string".into(),
+                        "is not".into(),
+                    ));
+                }
+            };
+
+            then_vec.push(Then { cond, reg, goto });
+        }
+        Ok(Some(then_vec))
+    }
+
+    fn _step_retry(&self, step_id: &str) -> Result<Option<Retry>, Error> {
+        let retry = self._step(step_id)["retry"].borrow();
+        if retry.is_null() {
+            return Ok(None);
+        }
+
+        let path = format!("step.{}.retry", step_id);
+        let object = retry
+            .as_object()
+            .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+        let enable_keys = RETRY_KEYS;
+        for (k, _) in object {
+            if !enable_keys.contains(&k.as_str()) {
+                return Err(EntryUnexpected(path, k.into()));
+            }
+        }
+
+        let max = match &retry["max"] {
+            Value::Null => 3,
+            v => v.as_u64().filter(|m| *m > 0).ok_or_else(|| {
+                Violation(format!("{}.max", path), "> 0".into(), format!("is {}", v))
+            })?,
+        };
+
+        let delay = match &retry["delay"] {
+            Value::Null => 1,
+            v => v.as_u64().ok_or_else(|| {
+                Violation(format!("{}.delay", path), ">= 0".into(), format!("is {}", v))
+            })?,
+        };
+
+        let backoff = match &retry["backoff"] {
+            Value::Null => "fixed",
+            v => v.as_str().ok_or_else(|| {
+                Violation(format!("{}.backoff", path), "be a string".into(), format!("is {}", v))
+            })?,
+        };
+        match backoff {
+            "fixed" => (),
+            "exponential" => (),
+            o => return Err(ValueUnexpected(format!("{}.backoff", path), o.into())),
+        }
+
+        let cond = match &retry["cond"] {
+            Value::Null => None,
+            Value::String(c) => Some(c.trim().to_string()),
+            _ => {
+                return Err(Violation(
+                    format!("{}.cond", path),
+                    "be a string".into(),
+                    "is not".into(),
+                ));
+            }
+        };
+
+        Ok(Some(Retry {
+            max: max as usize,
+            delay: Duration::from_secs(delay),
+            backoff: backoff.into(),
+            cond,
+        }))
+    }
+
+    fn _step_timeout(&self, step_id: &str) -> Result<Option<Duration>, Error> {
+        self._timeout(
+            format!("step.{}.timeout", step_id),
+            &self._step(step_id)["timeout"],
+        )
+    }
+
+    fn _step_action_timeout(
+        &self,
+        step_id: &str,
+        action_id: &str,
+    ) -> Result<Option<Duration>, Error> {
+        self._step_entry_timeout(step_id, "action", action_id)
+    }
+
+    fn _step_defer_id_vec(&self, step_id: &str) -> Result<Vec<&str>, Error> {
+        let defer = self._step(step_id)["defer"].borrow();
+        if defer.is_null() {
+            return Ok(vec![]);
+        }
+        defer
+            .as_object()
+            .map(|d| d.keys().map(|k| k.as_str()).collect())
+            .ok_or_else(|| {
+                Violation(
+                    format!("step.{}.defer", step_id),
+                    "be a object".into(),
+                    "is not".into(),
+                )
+            })
+    }
+
+    /// timeout of an action under `entry` of the step, `action` or `defer`
+    fn _step_entry_timeout(
+        &self,
+        step_id: &str,
+        entry: &str,
+        action_id: &str,
+    ) -> Result<Option<Duration>, Error> {
+        let action_obj = self._step_entry_obj(step_id, entry, action_id)?;
+        self._timeout(
+            format!("step.{}.{}.{}.timeout", step_id, entry, action_id),
+            action_obj.get("timeout").unwrap_or(&Value::Null),
+        )
+    }
+
+    /// `expect_error` of an action under `entry` of the step, a string is the pattern of the code
+    fn _step_entry_expect_error(
+        &self,
+        step_id: &str,
+        entry: &str,
+        action_id: &str,
+    ) -> Result<Option<ExpectError>, Error> {
+        let action_obj = self._step_entry_obj(step_id, entry, action_id)?;
+        let path = format!("step.{}.{}.{}.expect_error", step_id, entry, action_id);
+        let pattern = |key: &str, value: &Value| match value {
+            Value::Null => Ok(None),
+            Value::String(p) => Regex::new(p).map(Some).map_err(|e| {
+                Violation(
+                    format!("{}.{}", path, key),
+                    "be a regex".into(),
+                    format!("is not, {}", e),
+                )
+            }),
+            _ => Err(Violation(
+                format!("{}.{}", path, key),
+                "be a string".into(),
+                "is not".into(),
+            )),
+        };
+        match action_obj.get("expect_error").unwrap_or(&Value::Null) {
+            Value::Null => Ok(None),
+            code @ Value::String(_) => Ok(Some(ExpectError {
+                code: pattern("code", code)?,
+                message: None,
+            })),
+            Value::Object(object) => {
+                let enable_keys = EXPECT_ERROR_KEYS;
+                for (k, _) in object {
+                    if !enable_keys.contains(&k.as_str()) {
+                        return Err(EntryUnexpected(path, k.into()));
+                    }
+                }
+                let code = pattern("code", object.get("code").unwrap_or(&Value::Null))?;
+                let message = pattern("message", object.get("message").unwrap_or(&Value::Null))?;
+                Ok(Some(ExpectError { code, message }))
+            }
+            _ => Err(Violation(
+                path.clone(),
+                "be a string or a object".into(),
+                "is not".into(),
+            )),
+        }
+    }
+
+    fn _step_goto_check(&self, step_id_vec: &[&str], err_vec: &mut ErrorVec) {
+        for step_id in step_id_vec {
+            if let Ok(Some(then_vec)) = self._step_then(step_id) {
+                for then in then_vec {
+                    if let Some(goto) = then.goto() {
+                        if goto != GOTO_END && !step_id_vec.contains(&goto) {
+                            err_vec.push(ValueUnexpected(
+                                format!("step.{}.then.goto", step_id),
+                                goto.into(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn _step_action_obj(&self, step_id: &str, action_id: &str) -> Result<&Map, Error> {
+        self._step_entry_obj(step_id, "action", action_id)
+    }
+
+    fn _step_entry_obj(&self, step_id: &str, entry: &str, action_id: &str) -> Result<&Map, Error> {
+        let path = format!("step.{}.{}.{}", step_id, entry, action_id);
+        let obj = self._step(step_id)[entry][action_id]
+            .as_object()
+            .ok_or_else(|| Violation(path.clone(), "be a object".into(), "is not".into()))?;
+        let func_count = obj
+            .keys()
+            .filter(|k| !ACTION_OPTION_KEYS.contains(&k.as_str()))
+            .count();
+        return if func_count != 1 {
+            Err(Violation(path, "have 1 entry".into(), "is not".into()))
         } else {
             Ok(obj)
         };
     }
 
     fn _step_action_func(&self, step_id: &str, action_id: &str) -> Result<&str, Error> {
-        let action_obj = self._step_action_obj(step_id, action_id)?;
-        let only = action_obj.iter().last().unwrap();
+        let only = self._step_action_only(step_id, action_id)?;
         Ok(only.0.as_str())
     }
 
     fn _step_action_args(&self, step_id: &str, action_id: &str) -> Result<&Value, Error> {
-        let action_obj = self._step_action_obj(step_id, action_id)?;
-        let only = action_obj.iter().last().unwrap();
+        let only = self._step_action_only(step_id, action_id)?;
         Ok(only.1)
     }
+
+    fn _step_action_only(
+        &self,
+        step_id: &str,
+        action_id: &str,
+    ) -> Result<(&String, &Value), Error> {
+        self._step_entry_only(step_id, "action", action_id)
+    }
+
+    fn _step_entry_only(
+        &self,
+        step_id: &str,
+        entry: &str,
+        action_id: &str,
+    ) -> Result<(&String, &Value), Error> {
+        let action_obj = self._step_entry_obj(step_id, entry, action_id)?;
+        let only = action_obj
+            .iter()
+            .find(|(k, _)| !ACTION_OPTION_KEYS.contains(&k.as_str()))
+            .unwrap();
+        Ok(only)
+    }
+}
+
+/// stages of the first cycle reachable from `stage_id`, the stage closing it comes twice
+fn stage_cycle_find<'f>(
+    stage_id: &'f str,
+    depends_on_map: &HashMap<&'f str, Vec<&'f str>>,
+    visited: &mut HashSet<&'f str>,
+    stack: &mut Vec<&'f str>,
+) -> Option<Vec<&'f str>> {
+    if let Some(idx) = stack.iter().position(|s| *s == stage_id) {
+        let mut cycle = stack[idx..].to_vec();
+        cycle.push(stage_id);
+        return Some(cycle);
+    }
+    if !visited.insert(stage_id) {
+        return None;
+    }
+    stack.push(stage_id);
+    for depends_on in depends_on_map.get(stage_id).into_iter().flatten() {
+        if let Some(cycle) = stage_cycle_find(depends_on, depends_on_map, visited, stack) {
+            return Some(cycle);
+        }
+    }
+    stack.pop();
+    None
+}
+
+/// moves the actions of each 0.0.1 step into its `action` entry
+fn step_map_upgrade(step_map: &mut Value) -> Result<(), Error> {
+    let step_map = match step_map {
+        Value::Object(step_map) => step_map,
+        _ => return Ok(()),
+    };
+    for (step_id, step) in step_map.iter_mut() {
+        if let Value::Object(step_obj) = step {
+            let mut upgraded = Map::new();
+            let mut action = Map::new();
+            for (k, v) in std::mem::take(step_obj) {
+                if !STEP_OPTION_KEYS.contains(&k.as_str()) {
+                    action.insert(k, v);
+                } else if step_option_like(k.as_str(), &v) {
+                    upgraded.insert(k, v);
+                } else {
+                    return Err(Violation(
+                        format!("step.{}.{}", step_id, k),
+                        "be a step option since 0.0.2, rename the action before the upgrade".into(),
+                        "is an action".into(),
+                    ));
+                }
+            }
+            upgraded.insert("action".into(), Value::Object(action));
+            *step_obj = upgraded;
+        }
+    }
+    Ok(())
+}
+
+/// whether a 0.0.1 step entry named after a step option has the form of that option
+fn step_option_like(key: &str, value: &Value) -> bool {
+    match key {
+        "then" => value.is_array(),
+        "timeout" => value.is_u64(),
+        "retry" => value
+            .as_object()
+            .map(|r| r.keys().all(|k| RETRY_KEYS.contains(&k.as_str())))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// schema of an object allowing only `keys`, the entries come from the key table so they follow
+/// what `Flow::check` accepts, `properties` must describe each of them and nothing else
+fn object_schema(keys: &[&str], properties: Value) -> Value {
+    let mut property_map = Map::new();
+    for key in keys {
+        let property = properties
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| panic!("schema of `{}` lost", key));
+        property_map.insert(key.to_string(), property);
+    }
+    if let Some(unknown) = properties
+        .as_object()
+        .and_then(|p| p.keys().find(|k| !keys.contains(&k.as_str())))
+    {
+        panic!("schema of `{}` is not in the key table", unknown);
+    }
+    json!({
+        "type": "object",
+        "properties": property_map,
+        "additionalProperties": false,
+    })
+}
+
+/// errors collected by `Flow::check`, an error caused by an already collected one is dropped
+#[derive(Default)]
+struct ErrorVec {
+    err_vec: Vec<Error>,
+}
+
+impl ErrorVec {
+    fn push(&mut self, err: Error) {
+        let msg = err.to_string();
+        if !self.err_vec.iter().any(|e| e.to_string() == msg) {
+            self.err_vec.push(err);
+        }
+    }
+
+    /// true if `result` is ok
+    fn check<T>(&mut self, result: Result<T, Error>) -> bool {
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                self.push(e);
+                false
+            }
+        }
+    }
+}
+
+impl From<ErrorVec> for Vec<Error> {
+    fn from(err_vec: ErrorVec) -> Self {
+        err_vec.err_vec
+    }
+}
+
+#[test]
+fn then_reg_reserved() {
+    let flow = json!({
+        "version": VERSION,
+        "stage": { "s": { "step": {
+            "a": { "action": { "x": { "let": 1 } }, "then": [{ "reg": { "n": 1, "step": 2 } }] },
+            "b": { "action": { "x": { "let": 1 } }, "then": [{ "reg": { "n": 1 }, "goto": "a" }] }
+        } } }
+    });
+    let err_vec: Vec<String> = Flow::check(flow, Path::new("."))
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        err_vec,
+        vec!["step.a.then.0.reg.step must not be a name of the case context but it is"]
+    );
+}
+
+#[test]
+fn retry_delay() {
+    let flow = json!({
+        "version": VERSION,
+        "stage": { "s": { "step": {
+            "a": { "action": { "x": { "let": 1 } }, "retry": { "max": 4, "delay": 2, "backoff": "exponential" } },
+            "b": { "action": { "x": { "let": 1 } }, "retry": { "delay": 2 } }
+        } } }
+    });
+    let flow = Flow::new(flow, Path::new(".")).unwrap();
+
+    let exponential = flow.step_retry("a").unwrap();
+    assert_eq!(exponential.max(), 4);
+    let delay_vec: Vec<u64> = (1..4).map(|a| exponential.delay(a).as_secs()).collect();
+    assert_eq!(delay_vec, vec![2, 4, 8]);
+
+    let fixed = flow.step_retry("b").unwrap();
+    assert_eq!(fixed.max(), 3);
+    assert_eq!(fixed.delay(3).as_secs(), 2);
+
+    let backoff_err = |backoff: Value| -> Vec<String> {
+        let flow = json!({
+            "version": VERSION,
+            "stage": { "s": { "step": {
+                "a": { "action": { "x": { "let": 1 } }, "retry": { "backoff": backoff } }
+            } } }
+        });
+        Flow::check(flow, Path::new(".")).iter().map(|e| e.to_string()).collect()
+    };
+    assert_eq!(
+        backoff_err(json!(2)),
+        vec!["step.a.retry.backoff must be a string but it is 2"]
+    );
+    assert_eq!(backoff_err(json!("linear")).len(), 1);
+}
+
+#[test]
+fn stage_arrival() {
+    let stage = |arrival: Value| {
+        json!({
+            "version": VERSION,
+            "stage": { "s": {
+                "arrival": arrival,
+                "concurrency": 2,
+                "step": { "a": { "action": { "x": { "let": 1 } } } }
+            } }
+        })
+    };
+    let flow = json!({
+        "version": VERSION,
+        "stage": { "s": {
+            "arrival": { "rate": 20 },
+            "step": { "a": { "action": { "x": { "let": 1 } } } }
+        } }
+    });
+    let arrival = Flow::new(flow, Path::new(".")).unwrap().stage_arrival("s").unwrap();
+    assert_eq!(arrival.rate(), 20);
+    assert_eq!(arrival.max_in_flight(), 20);
+
+    let err_vec: Vec<String> = Flow::check(stage(json!({ "rate": 20 })), Path::new("."))
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(
+        err_vec,
+        vec!["stage.s.arrival must not be used with concurrency but it is"]
+    );
+}
+
+#[test]
+fn schema_key() {
+    let mut action_schema = Map::new();
+    action_schema.insert("let".into(), json!({}));
+    let schema = Flow::schema(&action_schema);
+    let defs = schema["$defs"].as_object().unwrap();
+    for (path, keys) in [
+        ("/properties", ROOT_KEYS),
+        ("/properties/stage/additionalProperties/properties", STAGE_KEYS),
+        ("/$defs/step/properties", STEP_KEYS),
+    ] {
+        let property_key_vec: Vec<&str> = schema
+            .pointer(path)
+            .and_then(|p| p.as_object())
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(property_key_vec.len(), keys.len());
+        assert!(keys.iter().all(|k| property_key_vec.contains(k)));
+    }
+
+    let flow = json!({
+        "version": VERSION,
+        "stage": { "s": {
+            "concurrency": 2,
+            "step": { "a": { "action": { "x": { "let": 1, "timeout": 3 } }, "retry": { "max": 2 } } }
+        } }
+    });
+    assert!(Flow::check(flow.clone(), Path::new(".")).is_empty());
+    assert!(crate::schema::check("root", &flow, &schema, defs).is_empty());
+
+    let flow = json!({
+        "version": VERSION,
+        "stage": { "s": {
+            "step": { "a": { "action": { "x": { "let": 1 } }, "lost": 1 } }
+        } }
+    });
+    assert_eq!(Flow::check(flow.clone(), Path::new(".")).len(), 1);
+    assert_eq!(
+        crate::schema::check("root", &flow, &schema, defs)
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<String>>(),
+        vec!["root.stage.s.step.a unexpect entry `lost`"]
+    );
+}
+
+#[test]
+fn upgrade() {
+    let flow = json!({
+        "version": "0.0.1",
+        "pre": { "step": { "p": { "x": { "let": 1 } } } },
+        "stage": { "s": {
+            "pre": { "step": { "sp": { "x": { "let": 2 } } } },
+            "step": { "a": {
+                "x": { "let": 3 },
+                "y": { "log": "{{x}}", "timeout": 2 },
+                "then": [{ "goto": "a" }],
+                "retry": { "max": 2 },
+                "timeout": 5
+            } }
+        } }
+    });
+    assert_eq!(
+        Flow::upgrade(flow).unwrap(),
+        json!({
+            "version": "0.0.2",
+            "pre": { "step": { "p": { "action": { "x": { "let": 1 } } } } },
+            "stage": { "s": {
+                "pre": { "step": { "sp": { "action": { "x": { "let": 2 } } } } },
+                "step": { "a": {
+                    "action": { "x": { "let": 3 }, "y": { "log": "{{x}}", "timeout": 2 } },
+                    "then": [{ "goto": "a" }],
+                    "retry": { "max": 2 },
+                    "timeout": 5
+                } }
+            } }
+        })
+    );
+
+    let current = json!({ "version": VERSION, "stage": {} });
+    assert_eq!(Flow::upgrade(current.clone()).unwrap(), current);
+
+    let flow = json!({
+        "version": "0.0.1",
+        "stage": { "s": { "step": { "a": { "retry": { "let": 1 } } } } }
+    });
+    assert_eq!(
+        Flow::upgrade(flow.clone()).unwrap_err().to_string(),
+        "step.a.retry must be a step option since 0.0.2, rename the action before the upgrade but it is an action"
+    );
+    assert_eq!(Flow::check(flow, Path::new(".")).len(), 1);
+}
+
+#[test]
+fn stage_depends_on() {
+    let stage = |stage_id: &str, depends_on: Value| {
+        let mut stage = json!({ "step": { stage_id: { "action": { "x": { "let": 1 } } } } });
+        if !depends_on.is_null() {
+            stage["depends_on"] = depends_on;
+        }
+        stage
+    };
+    let order = |stage_map: Value| -> Vec<(String, Vec<String>)> {
+        let flow = Flow::new(json!({ "version": VERSION, "stage": stage_map }), Path::new("."));
+        let flow = flow.unwrap();
+        flow.stage_id_vec()
+            .into_iter()
+            .map(|s| {
+                let depends_on = flow.stage_depends_on(s).iter().map(|d| d.to_string()).collect();
+                (s.to_string(), depends_on)
+            })
+            .collect()
+    };
+
+    // one after another without depends_on
+    let mut stage_map = Map::new();
+    for stage_id in ["a", "b", "c"] {
+        stage_map.insert(stage_id.into(), stage(stage_id, Value::Null));
+    }
+    assert_eq!(
+        order(Value::Object(stage_map.clone())),
+        vec![
+            ("a".to_string(), vec![]),
+            ("b".to_string(), vec!["a".to_string()]),
+            ("c".to_string(), vec!["b".to_string()]),
+        ]
+    );
+
+    // once a stage uses it, the others depend on nothing
+    stage_map.insert("a".into(), stage("a", json!(["c"])));
+    assert_eq!(
+        order(Value::Object(stage_map.clone())),
+        vec![
+            ("a".to_string(), vec!["c".to_string()]),
+            ("b".to_string(), vec![]),
+            ("c".to_string(), vec![]),
+        ]
+    );
+
+    stage_map.insert("c".into(), stage("c", json!(["b"])));
+    stage_map.insert("b".into(), stage("b", json!(["a"])));
+    let err_vec: Vec<String> = Flow::check(
+        json!({ "version": VERSION, "stage": stage_map }),
+        Path::new("."),
+    )
+    .iter()
+    .map(|e| e.to_string())
+    .collect();
+    assert_eq!(
+        err_vec,
+        vec!["stage.b.depends_on must not be cyclic but it is a -> c -> b -> a"]
+    );
+
+    let err_vec: Vec<String> = Flow::check(
+        json!({ "version": VERSION, "stage": { "a": stage("a", json!(["a"])), "b": stage("b", json!(["x"])) } }),
+        Path::new("."),
+    )
+    .iter()
+    .map(|e| e.to_string())
+    .collect();
+    assert_eq!(
+        err_vec,
+        vec![
+            "stage.a.depends_on unexpect value a",
+            "stage.b.depends_on unexpect value x",
+        ]
+    );
 }
@@ -1,8 +1,14 @@
+pub use fs::copy;
 pub use fs::create_dir_all;
 pub use fs::metadata;
+pub use fs::read;
 pub use fs::read_dir;
+pub use fs::read_to_string;
+pub use fs::remove_dir;
+pub use fs::remove_dir_all;
 pub use fs::remove_file;
 pub use fs::rename;
+pub use fs::write;
 pub use fs::DirEntry;
 pub use fs::File;
 pub use fs::OpenOptions;
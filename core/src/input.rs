@@ -24,4 +24,9 @@ pub trait TaskLoader: Sync + Send {
 #[async_trait]
 pub trait StageLoader: Sync + Send {
     async fn load(&mut self, size: usize) -> Result<Vec<(String, Value)>, Error>;
+
+    /// field names every case has, if they are known before loading
+    async fn fields(&mut self) -> Result<Option<Vec<String>>, Error> {
+        Ok(None)
+    }
 }
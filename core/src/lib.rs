@@ -5,6 +5,8 @@ pub mod flow;
 pub mod future;
 pub mod input;
 pub mod output;
+pub mod schema;
+pub mod stat;
 pub mod step;
 pub mod task;
 pub mod value;
@@ -24,6 +24,11 @@ pub trait TaskReporter: Sync + Send {
 
     async fn start(&mut self, time: DateTime<Utc>) -> Result<(), Error>;
 
+    /// the case of the `post` steps, reported apart from the stages before the task ends
+    async fn post(&mut self, _post_asset: &dyn CaseAsset) -> Result<(), Error> {
+        Ok(())
+    }
+
     async fn end(&mut self, task_asset: &dyn TaskAsset) -> Result<(), Error>;
 }
 
@@ -31,6 +36,10 @@ pub trait TaskReporter: Sync + Send {
 pub trait StageReporter: Sync + Send {
     async fn start(&mut self, time: DateTime<Utc>) -> Result<(), Error>;
 
+    async fn concurrency(&mut self, _time: DateTime<Utc>, _concurrency: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
     async fn report(&mut self, case_asset_vec: &Vec<Box<dyn CaseAsset>>) -> Result<(), Error>;
 
     async fn end(&mut self, task_asset: &dyn StageAsset) -> Result<(), Error>;
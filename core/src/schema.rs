@@ -0,0 +1,268 @@
+use regex::Regex;
+
+use crate::flow::Error;
+use crate::flow::Error::*;
+use crate::value::{Map, Value};
+
+/// errors of `value` found at `path` against `schema`, in the subset of JSON Schema used by
+/// `Flow::schema` and `Creator::schema`, a `$ref` of `#/$defs/<name>` is looked up in `defs`.
+/// a string holding a template may render to any value, so it is not checked
+pub fn check(path: &str, value: &Value, schema: &Value, defs: &Map) -> Vec<Error> {
+    let mut err_vec = vec![];
+    value_check(path, value, schema, defs, &mut err_vec);
+    err_vec
+}
+
+fn value_check(path: &str, value: &Value, schema: &Value, defs: &Map, err_vec: &mut Vec<Error>) {
+    if let Value::String(text) = value {
+        if text.contains("{{") {
+            return;
+        }
+    }
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+    let err_count = err_vec.len();
+
+    if let Some(def) = schema
+        .get("$ref")
+        .and_then(|r| r.as_str())
+        .and_then(|r| r.strip_prefix("#/$defs/"))
+        .and_then(|name| defs.get(name))
+    {
+        value_check(path, value, def, defs, err_vec);
+    }
+
+    let type_vec: Vec<&str> = match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(t)) => t.iter().filter_map(|t| t.as_str()).collect(),
+        _ => vec![],
+    };
+    if !type_vec.is_empty() && !type_vec.iter().any(|t| type_is(value, t)) {
+        err_vec.push(Violation(
+            path.into(),
+            format!("be {}", type_vec.join(" or ")),
+            format!("is {}", type_of(value)),
+        ));
+        return;
+    }
+    if let Some(Value::Array(enum_vec)) = schema.get("enum") {
+        if !enum_vec.contains(value) {
+            err_vec.push(Violation(
+                path.into(),
+                format!("be one of {}", Value::Array(enum_vec.clone())),
+                format!("is {}", value),
+            ));
+        }
+    }
+    if let Some(c) = schema.get("const") {
+        if c != value {
+            err_vec.push(Violation(path.into(), format!("be {}", c), format!("is {}", value)));
+        }
+    }
+    if let (Some(min), Some(n)) = (schema.get("minimum").and_then(|m| m.as_f64()), value.as_f64())
+    {
+        if n < min {
+            err_vec.push(Violation(path.into(), format!(">= {}", min), format!("is {}", value)));
+        }
+    }
+    if let (Some(max), Some(n)) = (
+        schema.get("exclusiveMaximum").and_then(|m| m.as_f64()),
+        value.as_f64(),
+    ) {
+        if n >= max {
+            err_vec.push(Violation(path.into(), format!("< {}", max), format!("is {}", value)));
+        }
+    }
+
+    match value {
+        Value::Array(vec) => array_check(path, vec, schema, defs, err_vec),
+        Value::Object(map) => object_check(path, map, schema, defs, err_vec),
+        _ => {}
+    }
+
+    // the forms tell little more about a value already in error
+    if err_vec.len() > err_count {
+        return;
+    }
+    if let Some(Value::Array(one_of)) = schema.get("oneOf") {
+        let matched = one_of
+            .iter()
+            .filter(|s| check(path, value, s, defs).is_empty())
+            .count();
+        if matched != 1 {
+            err_vec.push(Violation(
+                path.into(),
+                "match exactly one of its forms".into(),
+                format!("matches {}", matched),
+            ));
+        }
+    }
+    if let Some(Value::Array(any_of)) = schema.get("anyOf") {
+        if !any_of.iter().any(|s| check(path, value, s, defs).is_empty()) {
+            err_vec.push(Violation(
+                path.into(),
+                "match one of its forms".into(),
+                "matches none".into(),
+            ));
+        }
+    }
+}
+
+fn array_check(path: &str, vec: &[Value], schema: &Map, defs: &Map, err_vec: &mut Vec<Error>) {
+    if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+        if (vec.len() as u64) < min {
+            err_vec.push(Violation(
+                path.into(),
+                format!("have at least {} items", min),
+                format!("has {}", vec.len()),
+            ));
+        }
+    }
+    if let Some(items) = schema.get("items") {
+        for (idx, v) in vec.iter().enumerate() {
+            value_check(format!("{}.{}", path, idx).as_str(), v, items, defs, err_vec);
+        }
+    }
+}
+
+fn object_check(path: &str, map: &Map, schema: &Map, defs: &Map, err_vec: &mut Vec<Error>) {
+    if let Some(min) = schema.get("minProperties").and_then(|m| m.as_u64()) {
+        if (map.len() as u64) < min {
+            err_vec.push(Violation(
+                path.into(),
+                format!("have at least {} entries", min),
+                format!("has {}", map.len()),
+            ));
+        }
+    }
+    if let Some(max) = schema.get("maxProperties").and_then(|m| m.as_u64()) {
+        if (map.len() as u64) > max {
+            err_vec.push(Violation(
+                path.into(),
+                format!("have at most {} entries", max),
+                format!("has {}", map.len()),
+            ));
+        }
+    }
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for key in required.iter().filter_map(|k| k.as_str()) {
+            if !map.contains_key(key) {
+                err_vec.push(EntryLost(path.into(), key.into()));
+            }
+        }
+    }
+
+    let name_pattern = schema
+        .get("propertyNames")
+        .and_then(|n| n["pattern"].as_str())
+        .and_then(|p| Regex::new(p).ok());
+    let name_enum = schema
+        .get("propertyNames")
+        .and_then(|n| n["enum"].as_array());
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for (k, v) in map {
+        let name_unexpected = name_pattern
+            .as_ref()
+            .map(|p| !p.is_match(k))
+            .unwrap_or(false)
+            || name_enum
+                .map(|e| !e.iter().any(|n| n.as_str() == Some(k.as_str())))
+                .unwrap_or(false);
+        if name_unexpected {
+            err_vec.push(EntryUnexpected(path.into(), k.into()));
+            continue;
+        }
+        let entry_path = format!("{}.{}", path, k);
+        match properties.and_then(|p| p.get(k)) {
+            Some(property) => value_check(entry_path.as_str(), v, property, defs, err_vec),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => err_vec.push(EntryUnexpected(path.into(), k.into())),
+                Some(additional) => {
+                    value_check(entry_path.as_str(), v, additional, defs, err_vec)
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+fn type_is(value: &Value, t: &str) -> bool {
+    match t {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        t => type_of(value) == t,
+    }
+}
+
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[test]
+fn args_check() {
+    use crate::value::json;
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "cmd": { "type": "array", "minItems": 1 },
+            "retry": { "type": ["integer", "string"], "minimum": 1 },
+            "mode": { "enum": ["a", "b"] },
+            "block": { "type": "object", "additionalProperties": { "$ref": "#/$defs/action" } }
+        },
+        "required": ["cmd"],
+        "additionalProperties": false
+    });
+    let mut defs = Map::new();
+    defs.insert(
+        "action".into(),
+        json!({
+            "type": "object",
+            "properties": { "sleep": { "type": "integer" }, "log": {} },
+            "propertyNames": { "enum": ["sleep", "log"] },
+            "oneOf": [{ "required": ["sleep"] }, { "required": ["log"] }]
+        }),
+    );
+    let err_vec = |args: Value| -> Vec<String> {
+        let mut err_vec: Vec<String> = check("x.program", &args, &schema, &defs)
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        err_vec.sort();
+        err_vec
+    };
+
+    assert!(err_vec(json!({ "cmd": ["ls"], "retry": "{{n}}", "mode": "b" })).is_empty());
+    assert!(err_vec(json!({ "cmd": "{{cmd}}", "block": { "s": { "sleep": "{{n}}" } } })).is_empty());
+    assert_eq!(
+        err_vec(json!({ "cmd": ["ls"], "block": { "s": { "sleep": 1, "log": 1 } } })),
+        vec!["x.program.block.s must match exactly one of its forms but it matches 2"]
+    );
+    assert_eq!(
+        err_vec(json!({ "cmd": [], "retry": 0, "mode": "c", "lost": 1 })),
+        vec![
+            "x.program unexpect entry `lost`",
+            "x.program.cmd must have at least 1 items but it has 0",
+            "x.program.mode must be one of [\"a\",\"b\"] but it is \"c\"",
+            "x.program.retry must >= 1 but it is 0",
+        ]
+    );
+    assert_eq!(
+        err_vec(json!({ "retry": 1.5, "block": { "s": { "sleep": "1" }, "l": { "let": 1 } } })),
+        vec![
+            "x.program lost entry `cmd`",
+            "x.program.block.l unexpect entry `let`",
+            "x.program.block.s.sleep must be integer but it is string",
+            "x.program.retry must be integer or string but it is number",
+        ]
+    );
+}
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::case::{CaseAsset, CaseState};
+use crate::step::StepState;
+
+/// values below `2 * SUB_BUCKET` micros are recorded exactly,
+/// larger ones with a relative error below 1/SUB_BUCKET
+const SUB_BUCKET: u64 = 64;
+
+/// latency histogram with log-linear buckets of microseconds
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    bucket_vec: Vec<u64>,
+    count: u64,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Histogram::default()
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let idx = bucket_index(latency.as_micros().min(u64::MAX as u128) as u64);
+        if self.bucket_vec.len() <= idx {
+            self.bucket_vec.resize(idx + 1, 0);
+        }
+        self.bucket_vec[idx] += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        if self.bucket_vec.len() < other.bucket_vec.len() {
+            self.bucket_vec.resize(other.bucket_vec.len(), 0);
+        }
+        for (idx, count) in other.bucket_vec.iter().enumerate() {
+            self.bucket_vec[idx] += count;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// `quantile` in `0.0..=1.0`
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((self.count as f64 * quantile).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (idx, count) in self.bucket_vec.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(bucket_upper(idx)).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.quantile(0.5)
+    }
+
+    pub fn p90(&self) -> Duration {
+        self.quantile(0.9)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.quantile(0.99)
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros < 2 * SUB_BUCKET {
+        return micros as usize;
+    }
+    let exp = 63 - micros.leading_zeros() as u64;
+    let shift = exp - 6;
+    let sub = (micros >> shift) - SUB_BUCKET;
+    (2 * SUB_BUCKET + (exp - 7) * SUB_BUCKET + sub) as usize
+}
+
+fn bucket_upper(idx: usize) -> u64 {
+    let idx = idx as u64;
+    if idx < 2 * SUB_BUCKET {
+        return idx;
+    }
+    let exp = (idx - 2 * SUB_BUCKET) / SUB_BUCKET + 7;
+    let sub = (idx - 2 * SUB_BUCKET) % SUB_BUCKET;
+    let shift = exp - 6;
+    let upper = (((SUB_BUCKET + sub + 1) as u128) << shift) - 1;
+    upper.min(u64::MAX as u128) as u64
+}
+
+/// counts and latency of one case, step or action
+#[derive(Debug, Clone, Default)]
+pub struct Stat {
+    ok: u64,
+    fail: u64,
+    histogram: Histogram,
+}
+
+impl Stat {
+    pub fn record(&mut self, ok: bool, latency: Duration) {
+        if ok {
+            self.ok += 1;
+        } else {
+            self.fail += 1;
+        }
+        self.histogram.record(latency);
+    }
+
+    pub fn merge(&mut self, other: &Stat) {
+        self.ok += other.ok;
+        self.fail += other.fail;
+        self.histogram.merge(&other.histogram);
+    }
+
+    pub fn ok(&self) -> u64 {
+        self.ok
+    }
+
+    pub fn fail(&self) -> u64 {
+        self.fail
+    }
+
+    pub fn count(&self) -> u64 {
+        self.ok + self.fail
+    }
+
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+}
+
+/// statistic of a stage or task, built from case assets as they finish
+#[derive(Debug, Clone)]
+pub struct Statistic {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    case: Stat,
+    step: BTreeMap<String, Stat>,
+    action: BTreeMap<(String, String), Stat>,
+}
+
+impl Statistic {
+    pub fn new(start: DateTime<Utc>) -> Statistic {
+        Statistic {
+            start,
+            end: start,
+            case: Stat::default(),
+            step: BTreeMap::new(),
+            action: BTreeMap::new(),
+        }
+    }
+
+    /// cancelled cases are not recorded
+    pub fn record(&mut self, ca: &dyn CaseAsset) {
+        let sa_vec = match ca.state() {
+            CaseState::Ok(sa_vec) | CaseState::Fail(sa_vec) => Some(sa_vec),
+            CaseState::Err(_) => None,
+            CaseState::Cancelled(_) => return,
+        };
+        self.case
+            .record(ca.state().is_ok(), latency(ca.start(), ca.end()));
+
+        for sa in sa_vec.into_iter().flat_map(|v| v.iter()) {
+            if sa.state().is_skipped() {
+                continue;
+            }
+            let step_id = sa.id().step();
+            self.step
+                .entry(step_id.to_string())
+                .or_default()
+                .record(sa.state().is_ok(), latency(sa.start(), sa.end()));
+
+            let aa_vec = match sa.state() {
+                StepState::Ok(aa_vec) | StepState::Fail(aa_vec) => aa_vec,
+                StepState::Skipped => continue,
+            };
+            for aa in aa_vec.iter() {
+                self.action
+                    .entry((step_id.to_string(), aa.id().to_string()))
+                    .or_default()
+                    .record(aa.state().is_ok(), latency(aa.start(), aa.end()));
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &Statistic) {
+        self.start = self.start.min(other.start);
+        self.end = self.end.max(other.end);
+        self.case.merge(&other.case);
+        for (k, v) in other.step.iter() {
+            self.step.entry(k.clone()).or_default().merge(v);
+        }
+        for (k, v) in other.action.iter() {
+            self.action.entry(k.clone()).or_default().merge(v);
+        }
+    }
+
+    pub fn end_at(&mut self, end: DateTime<Utc>) {
+        self.end = end;
+    }
+
+    pub fn case(&self) -> &Stat {
+        &self.case
+    }
+
+    pub fn step(&self, step_id: &str) -> Option<&Stat> {
+        self.step.get(step_id)
+    }
+
+    pub fn step_iter(&self) -> impl Iterator<Item = (&str, &Stat)> {
+        self.step.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn action(&self, step_id: &str, action_id: &str) -> Option<&Stat> {
+        self.action
+            .get(&(step_id.to_string(), action_id.to_string()))
+    }
+
+    pub fn action_iter(&self) -> impl Iterator<Item = (&str, &str, &Stat)> {
+        self.action
+            .iter()
+            .map(|((s, a), v)| (s.as_str(), a.as_str(), v))
+    }
+
+    /// finished cases per second
+    pub fn throughput(&self) -> f64 {
+        let elapsed = latency(self.start, self.end).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.case.count() as f64 / elapsed
+    }
+}
+
+fn latency(start: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+    (end - start).to_std().unwrap_or_default()
+}
+
+#[test]
+fn bucket_boundary() {
+    for micros in 0..2 * SUB_BUCKET {
+        assert_eq!(bucket_index(micros), micros as usize);
+        assert_eq!(bucket_upper(micros as usize), micros);
+    }
+    assert_eq!(bucket_index(128), 128);
+    assert_eq!(bucket_index(129), 128);
+    assert_eq!(bucket_index(130), 129);
+    assert_eq!(bucket_upper(128), 129);
+
+    // each bucket ends right before the next one starts
+    for idx in 0..bucket_index(u64::MAX) {
+        let upper = bucket_upper(idx);
+        assert_eq!(bucket_index(upper), idx);
+        assert_eq!(bucket_index(upper + 1), idx + 1);
+    }
+    assert_eq!(bucket_upper(bucket_index(u64::MAX)), u64::MAX);
+}
+
+#[test]
+fn histogram_quantile() {
+    let mut histogram = Histogram::new();
+    assert_eq!(histogram.p50(), Duration::ZERO);
+    for ms in 1..=1000 {
+        histogram.record(Duration::from_millis(ms));
+    }
+    assert_eq!(histogram.count(), 1000);
+    assert_eq!(histogram.max(), Duration::from_millis(1000));
+
+    let near = |actual: Duration, expect_ms: u64| {
+        let expect = Duration::from_millis(expect_ms);
+        actual >= expect && actual <= expect + expect / SUB_BUCKET as u32
+    };
+    assert!(near(histogram.p50(), 500), "p50 {:?}", histogram.p50());
+    assert!(near(histogram.p90(), 900), "p90 {:?}", histogram.p90());
+    assert!(near(histogram.p99(), 990), "p99 {:?}", histogram.p99());
+    assert_eq!(histogram.quantile(1.0), Duration::from_millis(1000));
+
+    let mut exact = Histogram::new();
+    for us in [3, 1, 2, 5, 4] {
+        exact.record(Duration::from_micros(us));
+    }
+    assert_eq!(exact.p50(), Duration::from_micros(3));
+    assert_eq!(exact.quantile(0.0), Duration::from_micros(1));
+}
+
+#[test]
+fn histogram_merge() {
+    let mut whole = Histogram::new();
+    let mut low = Histogram::new();
+    let mut high = Histogram::new();
+    for ms in 1..=1000 {
+        whole.record(Duration::from_millis(ms));
+        if ms <= 10 {
+            low.record(Duration::from_millis(ms));
+        } else {
+            high.record(Duration::from_millis(ms));
+        }
+    }
+
+    // the shorter bucket vec grows to hold the other one
+    low.merge(&high);
+    assert_eq!(low.count(), whole.count());
+    assert_eq!(low.max(), whole.max());
+    for quantile in [0.01, 0.5, 0.9, 0.99] {
+        assert_eq!(low.quantile(quantile), whole.quantile(quantile));
+    }
+
+    let mut empty = Histogram::new();
+    empty.merge(&Histogram::new());
+    assert_eq!(empty.count(), 0);
+}
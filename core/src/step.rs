@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 
@@ -13,6 +14,10 @@ pub trait ActionId: Sync + Send + Display {
     fn action(&self) -> &str;
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("timeout after {0:?}")]
+pub struct TimeoutError(pub Duration);
+
 pub enum ActionState {
     Ok(Asset),
     Err(crate::action::Error),
@@ -32,6 +37,13 @@ impl ActionState {
             _ => false,
         }
     }
+
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            ActionState::Err(e) => e.downcast_ref::<TimeoutError>().is_some(),
+            _ => false,
+        }
+    }
 }
 
 pub trait ActionAsset: Sync + Send {
@@ -57,6 +69,8 @@ pub trait StepId: Sync + Send + Display {
 pub enum StepState {
     Ok(TailDropVec<Box<dyn ActionAsset>>),
     Fail(TailDropVec<Box<dyn ActionAsset>>),
+    /// `if` of the step is not true, no action ran
+    Skipped,
 }
 
 impl StepState {
@@ -73,6 +87,10 @@ impl StepState {
             _ => false,
         }
     }
+
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, StepState::Skipped)
+    }
 }
 
 pub trait StepAsset: Sync + Send {
@@ -2,6 +2,8 @@ use std::fmt::Display;
 
 use chrono::{DateTime, Utc};
 
+use crate::stat::Statistic;
+
 pub type Error = Box<dyn std::error::Error + Sync + Send>;
 
 pub trait TaskId: Sync + Send + Display {
@@ -15,6 +17,7 @@ pub enum TaskState {
     Ok,
     Fail(String),
     Err(Error),
+    Cancelled(String),
 }
 
 impl TaskState {
@@ -35,6 +38,9 @@ pub trait TaskAsset: Sync + Send {
     fn end(&self) -> DateTime<Utc>;
 
     fn state(&self) -> &TaskState;
+
+    /// merged statistic of all stage rounds
+    fn stat(&self) -> &Statistic;
 }
 
 
@@ -50,6 +56,8 @@ pub enum StageState {
     Ok,
     Fail(String),
     Err(Error),
+    Timeout(String),
+    Cancelled(String),
 }
 
 impl StageState {
@@ -60,6 +68,28 @@ impl StageState {
             _ => false,
         }
     }
+
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            StageState::Timeout(_) => true,
+            _ => false,
+        }
+    }
+}
+
+pub trait ArrivalAsset: Sync + Send {
+    fn rate(&self) -> usize;
+
+    /// arrivals due in the stage, started or skipped
+    fn scheduled(&self) -> usize;
+
+    fn started(&self) -> usize;
+
+    /// started more than one arrival interval behind schedule
+    fn late(&self) -> usize;
+
+    /// not started because max_in_flight cases were running
+    fn skipped(&self) -> usize;
 }
 
 pub trait StageAsset: Sync + Send {
@@ -70,4 +100,8 @@ pub trait StageAsset: Sync + Send {
     fn end(&self) -> DateTime<Utc>;
 
     fn state(&self) -> &StageState;
+
+    fn arrival(&self) -> Option<&dyn ArrivalAsset>;
+
+    fn stat(&self) -> &Statistic;
 }
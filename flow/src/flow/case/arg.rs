@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
+use std::time::Duration;
 
 use chord_core::case::CaseId;
 use chord_core::collection::TailDropVec;
@@ -56,12 +57,21 @@ impl Display for CaseIdStruct {
     }
 }
 
+/// the contexts and limits a case runs with, besides its own data
+#[derive(Clone, Default)]
+pub struct CaseOption {
+    pub pre_ctx: Option<Arc<Map>>,
+    pub def_ctx: Option<Arc<Map>>,
+    pub timeout: Option<Duration>,
+}
+
 pub struct CaseArgStruct {
     flow: Arc<Flow>,
     step_vec: Arc<TailDropVec<(String, StepRunner)>>,
     id: Arc<CaseIdStruct>,
     data: Value,
     render_ctx: RenderContext,
+    timeout: Option<Duration>,
 }
 
 impl CaseArgStruct {
@@ -69,11 +79,15 @@ impl CaseArgStruct {
         flow: Arc<Flow>,
         step_vec: Arc<TailDropVec<(String, StepRunner)>>,
         data: Value,
-        pre_ctx: Option<Arc<Map>>,
-        def_ctx: Option<Arc<Map>>,
         stage_id: Arc<dyn StageId>,
         case: String,
+        option: CaseOption,
     ) -> CaseArgStruct {
+        let CaseOption {
+            pre_ctx,
+            def_ctx,
+            timeout,
+        } = option;
         let id = Arc::new(CaseIdStruct::new(stage_id, case));
 
         let mut render_data: Map = Map::new();
@@ -96,6 +110,7 @@ impl CaseArgStruct {
             id,
             data,
             render_ctx,
+            timeout,
         };
     }
 
@@ -129,6 +144,36 @@ impl CaseArgStruct {
         }
     }
 
+    /// the ended task as `task`, seen by the `post` steps
+    pub fn task_register(&mut self, task: Value) {
+        if let Value::Object(ctx) = self.render_ctx.data_mut() {
+            ctx.insert("task".to_string(), task);
+        }
+    }
+
+    /// the round of the stage with its `def` and `pre` as `stage`
+    pub fn stage_register(&mut self, stage: Value) {
+        if let Value::Object(ctx) = self.render_ctx.data_mut() {
+            ctx.insert("stage".to_string(), stage);
+        }
+    }
+
+    pub fn step_then_register(&mut self, reg: Map) {
+        if let Value::Object(ctx) = self.render_ctx.data_mut() {
+            for (k, v) in reg {
+                ctx.insert(k, v);
+            }
+        }
+    }
+
+    pub fn flow(&self) -> &Flow {
+        self.flow.as_ref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
     pub fn id(&self) -> Arc<CaseIdStruct> {
         self.id.clone()
     }
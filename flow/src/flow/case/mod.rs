@@ -1,64 +1,483 @@
+use std::time::Instant;
+
 use chrono::Utc;
-use log::{info, trace, warn};
+use futures::future::{select, Either};
+use handlebars::RenderError;
+use log::{error, info, trace, warn};
 use tracing::{error_span, Instrument};
 
+use chord_core::action::{Arg, Context};
 use chord_core::case::{CaseId, CaseState};
 use chord_core::collection::TailDropVec;
+use chord_core::flow::{Flow, GOTO_END};
+use chord_core::future::time::timeout;
 use chord_core::step::StepAsset;
+use chord_core::value::Map;
+use Error::*;
 use res::CaseAssetStruct;
 
+use crate::flow::{assign_by_render, render_str};
 use crate::flow::case::arg::CaseArgStruct;
-use crate::flow::step::StepRunner;
-use crate::model::app::App;
+use crate::flow::step::{Deadline, StepRunner};
+use crate::flow::task::cancel::CancelToken;
+use crate::flow::task::{CANCELLED, DURATION_EXCEEDED};
+use crate::model::app::{App, RenderContext};
 
 pub mod arg;
 pub mod res;
 
+/// jumps a case may take, a `goto` back to an earlier step could loop forever
+static GOTO_MAX: usize = 10000;
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error("step `{0}` then:\n{1}")]
+    Then(String, RenderError),
+
+    #[error("step `{0}` goto more than {1} times")]
+    GotoExceeded(String, usize),
+}
+
 pub async fn run(flow_ctx: &dyn App, arg: CaseArgStruct) -> CaseAssetStruct {
     let trace_id = format!("{}", arg.id().case());
-    run0(flow_ctx, arg)
+    run0(flow_ctx, arg, None)
         .instrument(error_span!("case", case=trace_id))
         .await
 }
 
-async fn run0(flow_ctx: &dyn App, mut arg: CaseArgStruct) -> CaseAssetStruct {
+/// runs the case as `run` does, but its steps stop once `deadline` passes or the grace of `cancel`
+/// is over, the case is then `Cancelled` and the `defer` of its ok steps still run
+pub async fn run_until(
+    flow_ctx: &dyn App,
+    arg: CaseArgStruct,
+    deadline: Instant,
+    cancel: CancelToken,
+) -> CaseAssetStruct {
+    let trace_id = arg.id().case().to_string();
+    run0(flow_ctx, arg, Some((deadline, cancel)))
+        .instrument(error_span!("case", case=trace_id))
+        .await
+}
+
+async fn run0(
+    flow_ctx: &dyn App,
+    mut arg: CaseArgStruct,
+    stop: Option<(Instant, CancelToken)>,
+) -> CaseAssetStruct {
     trace!("case run");
     let start = Utc::now();
+    let mut defer_idx_vec = vec![];
+    let state = match stop {
+        None => step_vec_run(flow_ctx, &mut arg, &mut defer_idx_vec).await,
+        Some((deadline, cancel)) => {
+            let remain = deadline.saturating_duration_since(Instant::now());
+            let run = timeout(remain, step_vec_run(flow_ctx, &mut arg, &mut defer_idx_vec));
+            match select(Box::pin(run), Box::pin(cancel.grace_over())).await {
+                Either::Left((Ok(state), _)) => state,
+                Either::Left((Err(_), _)) => CaseState::Cancelled(DURATION_EXCEEDED.to_string()),
+                Either::Right(_) => CaseState::Cancelled(CANCELLED.to_string()),
+            }
+        }
+    };
+    match &state {
+        CaseState::Ok(_) => info!("case Ok"),
+        CaseState::Fail(_) => warn!("case Fail"),
+        CaseState::Cancelled(_) => warn!("case Cancelled"),
+        CaseState::Err(_) => error!("case Err"),
+    }
+
+    let defer_asset_vec = defer_run(flow_ctx, &mut arg, defer_idx_vec).await;
+    CaseAssetStruct::new(
+        arg.id().clone(),
+        start,
+        Utc::now(),
+        arg.take_data(),
+        state,
+    )
+    .defer(defer_asset_vec)
+}
+
+/// runs the steps until one fails, `defer_idx_vec` collects the ok steps with a `defer`
+async fn step_vec_run(
+    flow_ctx: &dyn App,
+    arg: &mut CaseArgStruct,
+    defer_idx_vec: &mut Vec<usize>,
+) -> CaseState {
     let mut step_asset_vec = Vec::<Box<dyn StepAsset>>::new();
     let step_vec = arg.step_vec().clone();
+    let deadline = arg.timeout().map(Deadline::after);
 
-    for (step_id, step_runner) in step_vec.iter() {
+    let mut step_idx = 0;
+    let mut goto_count = 0;
+    while let Some((step_id, step_runner)) = step_vec.get(step_idx) {
         let step_runner: &StepRunner = step_runner;
 
         let mut step_arg = arg.step_arg_create(step_id, flow_ctx);
 
-        let step_asset = step_runner.run(&mut step_arg)
+        let step_asset = step_runner.run(&mut step_arg, deadline)
             .instrument(error_span!("step", step=step_id))
             .await;
 
+        // a skipped step leaves no trace in `step.*` and takes no branch
+        if step_asset.state().is_skipped() {
+            step_asset_vec.push(Box::new(step_asset));
+            step_idx += 1;
+            continue;
+        }
         if !step_asset.state().is_ok() {
             step_asset_vec.push(Box::new(step_asset));
-            warn!("case Fail");
-            return CaseAssetStruct::new(
-                arg.id().clone(),
-                start,
-                Utc::now(),
-                arg.take_data(),
-                CaseState::Fail(TailDropVec::from(step_asset_vec)),
-            );
+            return CaseState::Fail(TailDropVec::from(step_asset_vec));
+        }
+        if step_runner.has_defer() {
+            defer_idx_vec.push(step_idx);
+        }
+
+        // branches depend on action values, a dry run goes through the steps in order
+        let then = if step_runner.dry_run() {
+            Ok((None, None))
         } else {
-            arg.step_asset_register(step_asset.id().step(), &step_asset)
-                .await;
-            step_asset_vec.push(Box::new(step_asset));
+            step_then(flow_ctx, arg.flow(), step_id, step_arg.context())
+                .map_err(|e| Then(step_id.to_string(), e))
+        };
+
+        arg.step_asset_register(step_asset.id().step(), &step_asset)
+            .await;
+        step_asset_vec.push(Box::new(step_asset));
+
+        let goto = match then {
+            Ok((reg, goto)) => {
+                if let Some(reg) = reg {
+                    arg.step_then_register(reg);
+                }
+                goto
+            }
+            Err(e) => return CaseState::Err(Box::new(Box::new(e))),
+        };
+
+        step_idx = match goto {
+            None => step_idx + 1,
+            Some(goto) if goto == GOTO_END => break,
+            Some(_) if goto_count == GOTO_MAX => {
+                let e = GotoExceeded(step_id.to_string(), GOTO_MAX);
+                return CaseState::Err(Box::new(Box::new(e)));
+            }
+            Some(goto) => {
+                trace!("step goto {}", goto);
+                goto_count += 1;
+                step_vec
+                    .iter()
+                    .position(|(sid, _)| sid == &goto)
+                    .unwrap()
+            }
+        };
+    }
+
+    CaseState::Ok(TailDropVec::from(step_asset_vec))
+}
+
+/// runs the `defer` of the steps in `defer_idx_vec` last first, with the context the case ended in
+async fn defer_run(
+    flow_ctx: &dyn App,
+    arg: &mut CaseArgStruct,
+    defer_idx_vec: Vec<usize>,
+) -> Vec<Box<dyn StepAsset>> {
+    let step_vec = arg.step_vec().clone();
+    let mut defer_asset_vec = Vec::<Box<dyn StepAsset>>::with_capacity(defer_idx_vec.len());
+    for step_idx in defer_idx_vec.into_iter().rev() {
+        let (step_id, step_runner) = &step_vec[step_idx];
+        let mut step_arg = arg.step_arg_create(step_id, flow_ctx);
+        step_arg.defer();
+        let defer_asset = step_runner
+            .defer_run(&mut step_arg)
+            .instrument(error_span!("defer", step = step_id.as_str()))
+            .await;
+        if let Some(defer_asset) = defer_asset {
+            defer_asset_vec.push(Box::new(defer_asset));
         }
     }
+    defer_asset_vec
+}
 
-    info!("case Ok");
-    return CaseAssetStruct::new(
-        arg.id().clone(),
-        start,
-        Utc::now(),
-        arg.take_data(),
-        CaseState::Ok(TailDropVec::from(step_asset_vec)),
+fn step_then(
+    flow_ctx: &dyn App,
+    flow: &Flow,
+    step_id: &str,
+    context: &dyn Context,
+) -> Result<(Option<Map>, Option<String>), RenderError> {
+    let then_vec = match flow.step_then(step_id) {
+        Some(then_vec) => then_vec,
+        None => return Ok((None, None)),
+    };
+
+    let handlebars = flow_ctx.get_handlebars();
+    let render_ctx = RenderContext::wraps(context.data())?;
+    for then in then_vec {
+        if let Some(cond) = then.cond() {
+            let cond_tpl = format!("{{{{{cond}}}}}", cond = cond);
+            let cv = render_str(handlebars, &render_ctx, cond_tpl.as_str())?;
+            if !cv.eq("true") {
+                continue;
+            }
+        }
+
+        let reg = match then.reg() {
+            Some(reg) => Some(assign_by_render(handlebars, &render_ctx, reg, false)?),
+            None => None,
+        };
+        return Ok((reg, then.goto().map(|g| g.to_string())));
+    }
+
+    Ok((None, None))
+}
+
+/// an action recording its args in the order it runs, `fail` fails it,
+/// `{"sleep": <ms>}` sleeps, then records `{"end": <args>}` and gives the args back
+#[cfg(test)]
+pub(crate) struct Record {
+    pub(crate) value_vec: std::sync::Arc<std::sync::Mutex<Vec<chord_core::value::Value>>>,
+}
+
+#[cfg(test)]
+#[chord_core::action::async_trait]
+impl chord_core::action::Creator for Record {
+    async fn create(
+        &self,
+        _: &dyn chord_core::action::Chord,
+        _: &dyn Arg,
+    ) -> Result<Box<dyn chord_core::action::Action>, chord_core::action::Error> {
+        Ok(Box::new(Record {
+            value_vec: self.value_vec.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+#[chord_core::action::async_trait]
+impl chord_core::action::Action for Record {
+    async fn execute(
+        &self,
+        _: &dyn chord_core::action::Chord,
+        arg: &mut dyn Arg,
+    ) -> Result<chord_core::action::Asset, chord_core::action::Error> {
+        let args = arg.args()?;
+        self.value_vec.lock().unwrap().push(args.clone());
+        if args == "fail" {
+            return Err("fail".into());
+        }
+        let sleep = &args["sleep"];
+        let sleep = sleep.as_u64().or_else(|| sleep.as_str().and_then(|s| s.parse().ok()));
+        if let Some(ms) = sleep {
+            chord_core::future::time::sleep(std::time::Duration::from_millis(ms)).await;
+            let end = chord_core::value::json!({ "end": args.clone() });
+            self.value_vec.lock().unwrap().push(end);
+        }
+        Ok(chord_core::action::Asset::Value(args))
+    }
+}
+
+/// runs the steps of stage `s` of `flow` as one case with `data`, gives the case and the recorded args
+#[cfg(test)]
+async fn record_run(
+    flow: chord_core::value::Value,
+    data: chord_core::value::Value,
+) -> (CaseAssetStruct, Vec<chord_core::value::Value>) {
+    record_run_until(flow, data, None).await
+}
+
+/// as `record_run`, the case stops as `run_until` stops it if `stop` is given
+#[cfg(test)]
+async fn record_run_until(
+    flow: chord_core::value::Value,
+    data: chord_core::value::Value,
+    stop: Option<(Instant, CancelToken)>,
+) -> (CaseAssetStruct, Vec<chord_core::value::Value>) {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use chord_core::action::Creator;
+
+    use crate::flow::case::arg::{CaseIdStruct, CaseOption};
+    use crate::flow::step::arg::{ArgStruct, ChordStruct};
+    use crate::flow::task::arg::{StageIdStruct, TaskIdStruct};
+
+    let value_vec = Arc::new(Mutex::new(vec![]));
+    let mut creator_map: HashMap<String, Box<dyn Creator>> = HashMap::new();
+    creator_map.insert(
+        "record".into(),
+        Box::new(Record {
+            value_vec: value_vec.clone(),
+        }),
+    );
+    let app = crate::flow::app_create(creator_map).await;
+    let flow = Arc::new(Flow::new(flow, std::path::Path::new(".")).unwrap());
+    let task = Arc::new(TaskIdStruct::new("1".into(), "t".into()));
+    let stage = Arc::new(StageIdStruct::new(task, "s".into(), "1".into()));
+    let chord = Arc::new(ChordStruct::new(app.clone()));
+
+    let mut step_vec = vec![];
+    for sid in flow.stage_step_id_vec("s") {
+        let mut arg = ArgStruct::new(
+            app.as_ref(),
+            flow.as_ref(),
+            RenderContext::wraps(chord_core::value::json!({})).unwrap(),
+            Arc::new(CaseIdStruct::new(stage.clone(), "0".into())),
+            sid.to_string(),
+        );
+        let step = StepRunner::new(chord.clone(), &mut arg, false).await.unwrap();
+        step_vec.push((sid.to_string(), step));
+    }
+    let arg = CaseArgStruct::new(
+        flow.clone(),
+        Arc::new(TailDropVec::from(step_vec)),
+        data,
+        stage,
+        "1".into(),
+        CaseOption::default(),
+    );
+    let case_asset = match stop {
+        Some((deadline, cancel)) => run_until(app.as_ref(), arg, deadline, cancel).await,
+        None => run(app.as_ref(), arg).await,
+    };
+    let value_vec = value_vec.lock().unwrap().clone();
+    (case_asset, value_vec)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn defer_order() {
+    use chord_core::case::CaseAsset;
+    use chord_core::value::json;
+
+    let flow = |c: &str| {
+        json!({
+            "version": "0.0.2",
+            "stage": { "s": { "step": {
+                "a": {
+                    "action": { "x": { "record": "a" } },
+                    "defer": { "d": { "record": "defer a {{step.b.y}}" } }
+                },
+                "b": {
+                    "action": { "y": { "record": "b" } },
+                    "defer": { "e": { "record": "defer b" }, "f": { "record": "defer b {{e}}" } }
+                },
+                "c": {
+                    "action": { "z": { "record": c } },
+                    "defer": { "g": { "record": "defer c" } }
+                }
+            } } }
+        })
+    };
+
+    let (case_asset, value_vec) = record_run(flow("c"), json!({})).await;
+    assert!(case_asset.state().is_ok());
+    assert_eq!(
+        value_vec,
+        vec![
+            json!("a"),
+            json!("b"),
+            json!("c"),
+            json!("defer c"),
+            json!("defer b"),
+            json!("defer b defer b"),
+            json!("defer a b"),
+        ]
     );
+
+    // a failed step leaves its defer out, the ones of the steps before it still run
+    let (case_asset, value_vec) = record_run(flow("fail"), json!({})).await;
+    assert!(matches!(case_asset.state(), CaseState::Fail(_)));
+    assert_eq!(
+        value_vec,
+        vec![
+            json!("a"),
+            json!("b"),
+            json!("fail"),
+            json!("defer b"),
+            json!("defer b defer b"),
+            json!("defer a b"),
+        ]
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn step_if() {
+    use chord_core::case::CaseAsset;
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": {
+            "a": {
+                "if": "eq case.n 1",
+                "action": { "x": { "record": "a" } },
+                "defer": { "d": { "record": "defer a" } }
+            },
+            "b": {
+                "if": "eq case.n 2",
+                "action": { "y": { "record": "b" } }
+            },
+            "c": {
+                "action": { "z": { "record": "c" } }
+            }
+        } } }
+    });
+
+    let (case_asset, value_vec) = record_run(flow.clone(), json!({ "n": 1 })).await;
+    assert!(case_asset.state().is_ok());
+    assert_eq!(value_vec, vec![json!("a"), json!("c"), json!("defer a")]);
+
+    // a skipped step runs neither its actions nor its defer
+    let (case_asset, value_vec) = record_run(flow, json!({ "n": 2 })).await;
+    assert!(case_asset.state().is_ok());
+    assert_eq!(value_vec, vec![json!("b"), json!("c")]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn defer_cancelled() {
+    use std::time::Duration;
+
+    use chord_core::case::CaseAsset;
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": {
+            "a": {
+                "action": { "x": { "record": "a" } },
+                "defer": { "d": { "record": "defer a" } }
+            },
+            "b": {
+                "action": { "y": { "record": { "sleep": 5000 } } },
+                "defer": { "e": { "record": "defer b" } }
+            }
+        } } }
+    });
+    let expect_value_vec = vec![json!("a"), json!({ "sleep": 5000 }), json!("defer a")];
+
+    let deadline = Instant::now() + Duration::from_millis(100);
+    let stop = Some((deadline, CancelToken::never()));
+    let (case_asset, value_vec) = record_run_until(flow.clone(), json!({}), stop).await;
+    match case_asset.state() {
+        CaseState::Cancelled(cause) => assert_eq!(cause, DURATION_EXCEEDED),
+        _ => panic!("case past its deadline is not Cancelled"),
+    }
+    assert_eq!(value_vec, expect_value_vec);
+    assert_eq!(CaseAsset::defer(&case_asset).len(), 1);
+
+    let (trigger, cancel) = CancelToken::channel();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let run = record_run_until(flow, json!({}), Some((deadline, cancel)));
+    let cancel_later = async {
+        chord_core::future::time::sleep(Duration::from_millis(100)).await;
+        trigger.cancel(Duration::ZERO);
+    };
+    let ((case_asset, value_vec), _) = futures::join!(run, cancel_later);
+    match case_asset.state() {
+        CaseState::Cancelled(cause) => assert_eq!(cause, CANCELLED),
+        _ => panic!("cancelled case is not Cancelled"),
+    }
+    assert_eq!(value_vec, expect_value_vec);
+    assert_eq!(CaseAsset::defer(&case_asset).len(), 1);
 }
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use chrono::{DateTime, Utc};
 
 use chord_core::case::{CaseAsset, CaseId, CaseState};
+use chord_core::step::StepAsset;
 use chord_core::value::Value;
 
 use crate::flow::case::arg::CaseIdStruct;
@@ -13,6 +14,7 @@ pub struct CaseAssetStruct {
     end: DateTime<Utc>,
     data: Value,
     state: CaseState,
+    defer: Vec<Box<dyn StepAsset>>,
 }
 
 impl CaseAssetStruct {
@@ -29,8 +31,14 @@ impl CaseAssetStruct {
             end,
             data,
             state,
+            defer: vec![],
         }
     }
+
+    pub fn defer(mut self, defer: Vec<Box<dyn StepAsset>>) -> CaseAssetStruct {
+        self.defer = defer;
+        self
+    }
 }
 
 impl CaseAsset for CaseAssetStruct {
@@ -51,4 +59,8 @@ impl CaseAsset for CaseAssetStruct {
     fn state(&self) -> &CaseState {
         &self.state
     }
+
+    fn defer(&self) -> &[Box<dyn StepAsset>] {
+        &self.defer
+    }
 }
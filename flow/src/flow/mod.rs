@@ -10,13 +10,18 @@ use chord_core::action::prelude::Map;
 use chord_core::future::task::task_local;
 use chord_core::value::{from_str, Value};
 pub use task::arg::TaskIdStruct;
+pub use task::cancel::{CancelToken, CancelTrigger};
+pub use task::checkpoint::{Checkpoint, JobCheckpoint};
 pub use task::TaskRunner;
+pub use template::check as template_check;
+pub use template::Error as TemplateError;
 
 use crate::model::app::{App, AppStruct, RenderContext};
 
 mod case;
 mod step;
 mod task;
+mod template;
 
 task_local! {
     pub static CTX_ID: String;
@@ -106,6 +111,28 @@ fn render_value(
     }
 }
 
+/// like `render_value`, but a string which fails to render is kept as its raw template
+fn render_value_or_raw(handlebars: &Handlebars, render_ctx: &RenderContext, value: &mut Value) {
+    match value {
+        Value::String(v) => {
+            if let Ok(vr) = render_str(handlebars, render_ctx, v) {
+                let _ = replace(value, vr);
+            }
+        }
+        Value::Object(v) => {
+            for (_, v) in v.iter_mut() {
+                render_value_or_raw(handlebars, render_ctx, v);
+            }
+        }
+        Value::Array(v) => {
+            for i in v {
+                render_value_or_raw(handlebars, render_ctx, i);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
 fn assign_by_render(
     handlebars: &Handlebars,
     render_ctx: &RenderContext,
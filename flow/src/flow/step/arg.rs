@@ -77,6 +77,17 @@ impl ChordStruct {
         flow::render_value(handlebars, &rc, &mut val)?;
         Ok(val)
     }
+
+    fn render_or_raw(
+        handlebars: &Handlebars,
+        context: &dyn Context,
+        raw: &Value,
+    ) -> Result<Value, Error> {
+        let mut val = raw.clone();
+        let rc = RenderContext::wraps(context.data())?;
+        flow::render_value_or_raw(handlebars, &rc, &mut val);
+        Ok(val)
+    }
 }
 
 impl Chord for ChordStruct {
@@ -100,6 +111,8 @@ pub struct ArgStruct<'a, 'f> {
     context: ContextStruct,
     step_id: StepIdStruct,
     action_id: ActionIdStruct,
+    dry_run: bool,
+    defer: bool,
 }
 
 impl<'a, 'f> ArgStruct<'a, 'f> {
@@ -126,6 +139,8 @@ impl<'a, 'f> ArgStruct<'a, 'f> {
                 aid: "".to_string(),
                 step_id,
             },
+            dry_run: false,
+            defer: false,
         };
 
         return run_arg;
@@ -146,6 +161,21 @@ impl<'a, 'f> ArgStruct<'a, 'f> {
         &mut self.context
     }
 
+    /// args which fail to render keep their raw template,
+    /// as values of earlier actions are only placeholders in a dry run
+    pub fn dry_run(&mut self) {
+        self.dry_run = true;
+    }
+
+    /// args are read from the `defer` entry of the step instead of `action`
+    pub fn defer(&mut self) {
+        self.defer = true;
+    }
+
+    pub fn is_defer(&self) -> bool {
+        self.defer
+    }
+
     pub fn flow(&self) -> &Flow {
         self.flow
     }
@@ -161,12 +191,21 @@ impl<'a, 'f> Arg for ArgStruct<'a, 'f> {
     }
 
     fn args_raw(&self) -> &Value {
-        self.flow
-            .step_action_args(self.step_id().step(), self.action_id.aid.as_str())
+        if self.defer {
+            self.flow
+                .step_defer_args(self.step_id().step(), self.action_id.aid.as_str())
+        } else {
+            self.flow
+                .step_action_args(self.step_id().step(), self.action_id.aid.as_str())
+        }
     }
 
     fn args(&self) -> Result<Value, Error> {
-        ChordStruct::render(&self.app.get_handlebars(), &self.context, self.args_raw())
+        if self.dry_run {
+            ChordStruct::render_or_raw(self.app.get_handlebars(), &self.context, self.args_raw())
+        } else {
+            ChordStruct::render(self.app.get_handlebars(), &self.context, self.args_raw())
+        }
     }
 
     fn context_mut(&mut self) -> &mut dyn Context {
@@ -1,14 +1,20 @@
 use std::error::Error as StdError;
+use std::mem::replace;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc};
 use log::{debug, error, info, trace, warn};
 use tracing::{error_span, Instrument};
 
-use chord_core::action::{Action, Asset, Chord};
+use chord_core::action::{
+    async_trait, error_code_message, Action, Arg, Asset, Chord, Error as ActionError,
+};
 use chord_core::collection::TailDropVec;
-use chord_core::step::{ActionAsset, ActionState, StepId};
-use chord_core::value::Value;
+use chord_core::flow::{ExpectError, Retry};
+use chord_core::future::time::{sleep, timeout};
+use chord_core::step::{ActionAsset, ActionState, StepId, TimeoutError};
+use chord_core::value::{json, Value};
 use Error::*;
 use res::StepAssetStruct;
 
@@ -18,6 +24,15 @@ use crate::flow::step::res::ActionAssetStruct;
 pub mod arg;
 pub mod res;
 
+#[derive(thiserror::Error, Debug)]
+enum ExpectErrorMismatch {
+    #[error("expect an error of {0} but it succeeded")]
+    Succeeded(String),
+
+    #[error("expect an error of {0} but it is:\n{1}")]
+    Unmatched(String, String),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("unsupported action `{0}`")]
@@ -27,76 +42,137 @@ pub enum Error {
     Create(String, String, Box<dyn StdError + Sync + Send>),
 }
 
+type ActionVec = TailDropVec<(String, Option<Duration>, Option<ExpectError>, Box<dyn Action>)>;
+
+/// the instant a case, step or action must end by, with the configured timeout that set it
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline {
+            at: Instant::now() + timeout,
+            timeout,
+        }
+    }
+}
+
 pub struct StepRunner {
     chord: Arc<ChordStruct>,
-    action_vec: Arc<TailDropVec<(String, Box<dyn Action>)>>,
+    action_vec: Arc<ActionVec>,
+    defer_vec: Arc<ActionVec>,
+    if_cond: Option<String>,
+    retry: Option<Retry>,
+    timeout: Option<Duration>,
+    dry_run: bool,
 }
 
 impl StepRunner {
     pub async fn new(
         chord: Arc<ChordStruct>,
         arg: &mut ArgStruct<'_, '_>,
+        dry_run: bool,
     ) -> Result<StepRunner, Error> {
         trace!("step new");
-        let obj = arg.flow().step_obj(arg.step_id().step());
-        let aid_vec: Vec<String> = obj.iter().map(|(aid, _)| aid.to_string()).collect();
-        let mut action_vec = Vec::with_capacity(obj.len());
-
-        for aid in aid_vec {
-            arg.aid(aid.as_str());
-            let func = arg.flow().step_action_func(arg.step_id().step(), aid.as_str());
-            let action = chord
-                .creator(func.into())
-                .ok_or_else(|| Unsupported(func.into()))?
-                .create(chord.as_ref(), arg)
-                .await
-                .map_err(|e| Create(arg.step_id().step().to_string(), aid.to_string(), e))?;
-            action_vec.push((aid.to_string(), action));
-        }
+        let aid_vec: Vec<String> = arg
+            .flow()
+            .step_action_id_vec(arg.step_id().step())
+            .into_iter()
+            .map(|aid| aid.to_string())
+            .collect();
+        let action_vec = action_vec_create(chord.as_ref(), arg, aid_vec, dry_run).await?;
+
+        let did_vec: Vec<String> = arg
+            .flow()
+            .step_defer_id_vec(arg.step_id().step())
+            .into_iter()
+            .map(|did| did.to_string())
+            .collect();
+        arg.defer();
+        let defer_vec = action_vec_create(chord.as_ref(), arg, did_vec, dry_run).await?;
+
+        let if_cond = arg.flow().step_if(arg.step_id().step());
+        let retry = arg.flow().step_retry(arg.step_id().step());
+        let timeout = arg.flow().step_timeout(arg.step_id().step());
 
         Ok(StepRunner {
             chord,
             action_vec: Arc::new(TailDropVec::from(action_vec)),
+            defer_vec: Arc::new(TailDropVec::from(defer_vec)),
+            if_cond,
+            retry,
+            timeout,
+            dry_run,
         })
     }
 
-    pub async fn run(&self, arg: &mut ArgStruct<'_, '_>) -> StepAssetStruct {
+    pub fn has_defer(&self) -> bool {
+        !self.defer_vec.is_empty()
+    }
+
+    /// actions are explained instead of executed
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    pub async fn run(
+        &self,
+        arg: &mut ArgStruct<'_, '_>,
+        deadline: Option<Deadline>,
+    ) -> StepAssetStruct {
         trace!("step run");
         let start = Utc::now();
-        let mut asset_vec = Vec::with_capacity(self.action_vec.len());
-        let mut success = true;
-        for (aid, action) in self.action_vec.iter() {
-            let key: &str = aid;
-            let action: &Box<dyn Action> = action;
-            arg.aid(key);
-            let explain = action
-                .explain(self.chord.as_ref(), arg)
-                .await
-                .unwrap_or(Value::Null);
-            let start = Utc::now();
-            let value = action.execute(self.chord.as_ref(), arg)
-                .instrument(error_span!("action", action=key))
-                .await;
-            let end = Utc::now();
-            match value {
+        if self.dry_run {
+            let mut asset_vec = Vec::with_capacity(self.action_vec.len());
+            self.run_dry(self.action_vec.as_ref(), arg, &mut asset_vec).await;
+            return StepAssetStruct::new(Clone::clone(arg.step_id()), start, Utc::now(), asset_vec);
+        }
+        if let Some(cond) = self.if_cond.as_ref() {
+            let cond_tpl = format!("{{{{{cond}}}}}", cond = cond);
+            match self.chord.render(arg.context(), &Value::String(cond_tpl.clone())) {
+                Ok(cv) if cv.eq("true") => (),
                 Ok(_) => {
-                    let asset = action_asset(aid, start, end, explain, value);
-                    if let ActionState::Ok(v) = asset.state() {
-                        arg.context_mut()
-                            .data_mut()
-                            .insert(asset.id().to_string(), v.to_value());
-                    }
-                    asset_vec.push(asset);
+                    debug!("step skipped");
+                    return StepAssetStruct::skipped(Clone::clone(arg.step_id()), start, Utc::now());
                 }
-
-                Err(_) => {
-                    let asset = action_asset(aid, start, end, explain, value);
-                    asset_vec.push(asset);
-                    success = false;
-                    break;
+                Err(e) => {
+                    error!("step if:\n{}", e);
+                    let end = Utc::now();
+                    let asset = action_asset("if", start, end, Value::String(cond_tpl), Err(e));
+                    return StepAssetStruct::new(Clone::clone(arg.step_id()), start, end, vec![asset]);
                 }
             }
         }
+        let deadline = deadline_min(deadline, self.timeout.map(Deadline::after));
+        let mut asset_vec = Vec::with_capacity(self.action_vec.len());
+        let context_init = arg.context().data().clone();
+        let mut attempt = 1;
+        let success = loop {
+            let success = self
+                .run_once(self.action_vec.as_ref(), arg, &mut asset_vec, deadline)
+                .await;
+            let retry = match self.retry.as_ref() {
+                Some(retry) if attempt < retry.max() => retry,
+                _ => break success,
+            };
+            if !self.retry_need(retry, arg, attempt, &asset_vec, success) {
+                break success;
+            }
+
+            let delay = retry.delay(attempt);
+            if let Some(deadline) = deadline {
+                if Instant::now() + delay >= deadline.at {
+                    break success;
+                }
+            }
+            warn!("step retry {} after {:?}", attempt, delay);
+            sleep(delay).await;
+            attempt += 1;
+            let _ = replace(arg.context_mut().data_mut(), context_init.clone());
+        };
 
         if success {
             for ass in asset_vec.iter() {
@@ -133,6 +209,259 @@ impl StepRunner {
 
         StepAssetStruct::new(Clone::clone(arg.step_id()), start, Utc::now(), asset_vec)
     }
+
+    /// runs the `defer` actions of the step with the case context at its end, `None` if it has none
+    pub async fn defer_run(&self, arg: &mut ArgStruct<'_, '_>) -> Option<StepAssetStruct> {
+        if self.defer_vec.is_empty() {
+            return None;
+        }
+        trace!("step defer run");
+        let start = Utc::now();
+        let mut asset_vec = Vec::with_capacity(self.defer_vec.len());
+        if self.dry_run {
+            self.run_dry(self.defer_vec.as_ref(), arg, &mut asset_vec).await;
+        } else if self
+            .run_once(self.defer_vec.as_ref(), arg, &mut asset_vec, None)
+            .await
+        {
+            info!("step defer Ok");
+        } else {
+            error!("step defer Err");
+        }
+        Some(StepAssetStruct::new(Clone::clone(arg.step_id()), start, Utc::now(), asset_vec))
+    }
+
+    async fn run_once(
+        &self,
+        action_vec: &ActionVec,
+        arg: &mut ArgStruct<'_, '_>,
+        asset_vec: &mut Vec<ActionAssetStruct>,
+        deadline: Option<Deadline>,
+    ) -> bool {
+        for (aid, timeout, expect_error, action) in action_vec.iter() {
+            let key: &str = aid;
+            let action: &dyn Action = action.as_ref();
+            arg.aid(key);
+            let explain = action
+                .explain(self.chord.as_ref(), arg)
+                .await
+                .unwrap_or(Value::Null);
+            let start = Utc::now();
+            let deadline = deadline_min(deadline, timeout.map(Deadline::after));
+            let value = action_execute(self.chord.as_ref(), action, arg, deadline)
+                .instrument(error_span!("action", action=key))
+                .await;
+            let end = Utc::now();
+            let value = match expect_error {
+                Some(expect_error) => expect_error_check(expect_error, value),
+                None => value,
+            };
+            let explain = match &value {
+                Ok(Asset::Data(data)) => data.explain().unwrap_or(explain),
+                _ => explain,
+            };
+            match value {
+                Ok(_) => {
+                    let asset = action_asset(aid, start, end, explain, value);
+                    if let ActionState::Ok(v) = asset.state() {
+                        arg.context_mut()
+                            .data_mut()
+                            .insert(asset.id().to_string(), v.to_value());
+                    }
+                    asset_vec.push(asset);
+                }
+
+                Err(_) => {
+                    let asset = action_asset(aid, start, end, explain, value);
+                    asset_vec.push(asset);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// explain every action, the value of an action is a placeholder of its template path
+    async fn run_dry(
+        &self,
+        action_vec: &ActionVec,
+        arg: &mut ArgStruct<'_, '_>,
+        asset_vec: &mut Vec<ActionAssetStruct>,
+    ) {
+        arg.dry_run();
+        for (aid, _, _, action) in action_vec.iter() {
+            arg.aid(aid);
+            let start = Utc::now();
+            let explain = action.explain(self.chord.as_ref(), arg).await;
+            let end = Utc::now();
+            match explain {
+                Ok(explain) => {
+                    info!("{}:\n{}", arg.id(), explain_string(&explain));
+                    let placeholder = Value::String(format!(
+                        "{{{{step.{}.{}}}}}",
+                        arg.step_id().step(),
+                        aid
+                    ));
+                    arg.context_mut()
+                        .data_mut()
+                        .insert(aid.to_string(), placeholder.clone());
+                    let value = Ok(Asset::Value(placeholder));
+                    asset_vec.push(action_asset(aid, start, end, explain, value));
+                }
+                Err(e) => {
+                    error!("{}:\n{}", arg.id(), e);
+                    asset_vec.push(action_asset(aid, start, end, Value::Null, Err(e)));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn retry_need(
+        &self,
+        retry: &Retry,
+        arg: &mut ArgStruct<'_, '_>,
+        attempt: usize,
+        asset_vec: &[ActionAssetStruct],
+        success: bool,
+    ) -> bool {
+        let cond = match retry.cond() {
+            Some(cond) => cond,
+            None => return !success,
+        };
+
+        let error = match asset_vec.last().map(|a| a.state()) {
+            Some(ActionState::Err(e)) if !success => Value::String(e.to_string()),
+            _ => Value::Null,
+        };
+        arg.context_mut().data_mut().insert(
+            "retry".to_string(),
+            json!({
+                "attempt": attempt,
+                "error": error
+            }),
+        );
+
+        let cond_tpl = format!("{{{{{cond}}}}}", cond = cond);
+        let cv = self.chord.render(arg.context(), &Value::String(cond_tpl));
+        arg.context_mut().data_mut().remove("retry");
+        match cv {
+            Ok(cv) => cv.eq("true"),
+            Err(e) => {
+                error!("step retry cond:\n{}", e);
+                false
+            }
+        }
+    }
+}
+
+/// actions of `aid_vec` under `action` of the step, or under `defer` once `arg.defer()` is called
+/// a dry run does not create the actions, creating may connect or pull, only the creator explains them
+async fn action_vec_create(
+    chord: &ChordStruct,
+    arg: &mut ArgStruct<'_, '_>,
+    aid_vec: Vec<String>,
+    dry_run: bool,
+) -> Result<Vec<(String, Option<Duration>, Option<ExpectError>, Box<dyn Action>)>, Error> {
+    let mut action_vec = Vec::with_capacity(aid_vec.len());
+    for aid in aid_vec {
+        arg.aid(aid.as_str());
+        let flow = arg.flow();
+        let sid = arg.step_id().step();
+        let (func, timeout, expect_error) = if arg.is_defer() {
+            (
+                flow.step_defer_func(sid, &aid),
+                flow.step_defer_timeout(sid, &aid),
+                flow.step_defer_expect_error(sid, &aid),
+            )
+        } else {
+            (
+                flow.step_action_func(sid, &aid),
+                flow.step_action_timeout(sid, &aid),
+                flow.step_action_expect_error(sid, &aid),
+            )
+        };
+        let func = func.to_string();
+        let creator = chord
+            .creator(func.as_str())
+            .ok_or_else(|| Unsupported(func.clone()))?;
+        let action: Box<dyn Action> = if dry_run {
+            Box::new(DryAction { func })
+        } else {
+            creator
+                .create(chord, arg)
+                .await
+                .map_err(|e| Create(arg.step_id().step().to_string(), aid.to_string(), e))?
+        };
+        action_vec.push((aid, timeout, expect_error, action));
+    }
+    Ok(action_vec)
+}
+
+/// stands for an action in a dry run, never executed
+struct DryAction {
+    func: String,
+}
+
+#[async_trait]
+impl Action for DryAction {
+    async fn execute(&self, _: &dyn Chord, _: &mut dyn Arg) -> Result<Asset, ActionError> {
+        Err(Box::new(Unsupported(self.func.clone())))
+    }
+
+    async fn explain(&self, chord: &dyn Chord, arg: &dyn Arg) -> Result<Value, ActionError> {
+        match chord.creator(self.func.as_str()) {
+            Some(creator) => creator.explain(chord, arg).await,
+            None => Err(Box::new(Unsupported(self.func.clone()))),
+        }
+    }
+}
+
+async fn action_execute(
+    chord: &dyn Chord,
+    action: &dyn Action,
+    arg: &mut ArgStruct<'_, '_>,
+    deadline: Option<Deadline>,
+) -> Result<Asset, chord_core::action::Error> {
+    match deadline {
+        Some(deadline) => {
+            let remain = deadline.at.saturating_duration_since(Instant::now());
+            match timeout(remain, action.execute(chord, arg)).await {
+                Ok(value) => value,
+                Err(_) => Err(Box::new(TimeoutError(deadline.timeout))),
+            }
+        }
+        None => action.execute(chord, arg).await,
+    }
+}
+
+/// an error matching `expect_error` becomes the value of the action, a success fails it
+fn expect_error_check(
+    expect_error: &ExpectError,
+    value: Result<Asset, chord_core::action::Error>,
+) -> Result<Asset, chord_core::action::Error> {
+    let e = match value {
+        Ok(_) => return Err(Box::new(ExpectErrorMismatch::Succeeded(expect_error.to_string()))),
+        Err(e) => e,
+    };
+    let (code, message) = error_code_message(&e);
+    if expect_error.is_match(code.as_str(), message.as_str()) {
+        Ok(Asset::Value(json!({ "code": code, "message": message })))
+    } else {
+        Err(Box::new(ExpectErrorMismatch::Unmatched(
+            expect_error.to_string(),
+            e.to_string(),
+        )))
+    }
+}
+
+/// the deadline coming first, it keeps the timeout that set it
+fn deadline_min(a: Option<Deadline>, b: Option<Deadline>) -> Option<Deadline> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.at < a.at { b } else { a }),
+        (a, None) => a,
+        (None, b) => b,
+    }
 }
 
 fn action_asset(
@@ -179,3 +508,101 @@ pub fn action_asset_to_value(action_asset: &dyn ActionAsset) -> Value {
         }
     }
 }
+
+#[cfg(test)]
+struct CreateForbidden {}
+
+#[cfg(test)]
+#[async_trait]
+impl chord_core::action::Creator for CreateForbidden {
+    async fn create(&self, _: &dyn Chord, _: &dyn Arg) -> Result<Box<dyn Action>, ActionError> {
+        Err(Box::new(Unsupported("created in a dry run".into())))
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn dry_run_explain() {
+    use chord_core::action::Creator;
+    use chord_core::step::{StepAsset, StepState};
+    use std::collections::HashMap;
+
+    use crate::flow::case::arg::CaseIdStruct;
+    use crate::flow::task::arg::{StageIdStruct, TaskIdStruct};
+    use crate::model::app::RenderContext;
+
+    let mut creator_map: HashMap<String, Box<dyn Creator>> = HashMap::new();
+    creator_map.insert("forbidden".into(), Box::new(CreateForbidden {}));
+    let app = crate::flow::app_create(creator_map).await;
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": { "a": { "action": { "x": { "forbidden": "{{case.n}}" } } } } } }
+    });
+    let flow = chord_core::flow::Flow::new(flow, std::path::Path::new(".")).unwrap();
+    let task = Arc::new(TaskIdStruct::new("1".into(), "t".into()));
+    let stage = Arc::new(StageIdStruct::new(task, "s".into(), "1".into()));
+    let case = Arc::new(CaseIdStruct::new(stage, "1".into()));
+    let context = RenderContext::wraps(json!({ "case": { "n": "seven" } })).unwrap();
+    let arg_create = || ArgStruct::new(app.as_ref(), &flow, context.clone(), case.clone(), "a".into());
+
+    let chord = Arc::new(ChordStruct::new(app.clone()));
+    let step = StepRunner::new(chord, &mut arg_create(), true).await.unwrap();
+    let asset = step.run(&mut arg_create(), None).await;
+    match asset.state() {
+        StepState::Ok(av) => {
+            assert_eq!(av[0].explain(), &json!("seven"));
+            match av[0].state() {
+                ActionState::Ok(asset) => assert_eq!(asset.to_value(), json!("{{step.a.x}}")),
+                _ => panic!("dry run of action `x` is not Ok"),
+            }
+        }
+        _ => panic!("dry run of step `a` is not Ok"),
+    }
+}
+
+#[test]
+fn expect_error_match() {
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": { "a": { "action": {
+            "code": { "let": 1, "expect_error": "^10" },
+            "both": { "let": 1, "expect_error": { "code": "^100$", "message": "^fal" } }
+        } } } } }
+    });
+    let flow = chord_core::flow::Flow::new(flow, std::path::Path::new(".")).unwrap();
+    let check = |aid: &str, e: &str| {
+        let expect_error = flow.step_action_expect_error("a", aid).unwrap();
+        expect_error_check(&expect_error, Err(e.into())).map(|a| a.to_value())
+    };
+
+    assert_eq!(
+        check("code", r#"{"code":"104","message":"x"}"#).unwrap(),
+        json!({ "code": "104", "message": "x" })
+    );
+    assert!(check("code", r#"{"code":"204","message":"x"}"#).is_err());
+    assert!(check("code", "timeout after 1s").is_err());
+
+    assert_eq!(
+        check("both", r#"{"code":"100","message":"false"}"#).unwrap(),
+        json!({ "code": "100", "message": "false" })
+    );
+    assert!(check("both", r#"{"code":"100","message":"true"}"#).is_err());
+    assert!(check("both", r#"{"code":"1000","message":"false"}"#).is_err());
+
+    let succeeded = flow.step_action_expect_error("a", "both").unwrap();
+    let succeeded = expect_error_check(&succeeded, Ok(Asset::Value(json!(1))));
+    assert_eq!(
+        succeeded.err().unwrap().to_string(),
+        "expect an error of code `^100$`, message `^fal` but it succeeded"
+    );
+}
+
+#[test]
+fn deadline_first() {
+    let case = Deadline::after(Duration::from_secs(60));
+    let action = Deadline::after(Duration::from_secs(1));
+    assert_eq!(deadline_min(Some(case), Some(action)).unwrap().timeout, Duration::from_secs(1));
+    assert_eq!(deadline_min(Some(action), Some(case)).unwrap().timeout, Duration::from_secs(1));
+    assert_eq!(deadline_min(Some(case), None).unwrap().timeout, Duration::from_secs(60));
+    assert!(deadline_min(None, None).is_none());
+}
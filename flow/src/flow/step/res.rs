@@ -99,6 +99,15 @@ impl StepAssetStruct {
             state,
         }
     }
+
+    pub fn skipped(id: StepIdStruct, start: DateTime<Utc>, end: DateTime<Utc>) -> StepAssetStruct {
+        StepAssetStruct {
+            id,
+            start,
+            end,
+            state: StepState::Skipped,
+        }
+    }
 }
 
 impl StepAsset for StepAssetStruct {
@@ -0,0 +1,78 @@
+use std::time::{Duration, Instant};
+
+use futures::future::pending;
+
+use chord_core::future::sync::watch;
+use chord_core::future::time::sleep;
+
+/// receiving side of a cooperative cancellation, shared by every runner of a job
+#[derive(Clone)]
+pub struct CancelToken {
+    grace_end: watch::Receiver<Option<Instant>>,
+}
+
+/// sending side of a cooperative cancellation
+pub struct CancelTrigger {
+    grace_end: watch::Sender<Option<Instant>>,
+}
+
+impl CancelToken {
+    pub fn channel() -> (CancelTrigger, CancelToken) {
+        let (tx, rx) = watch::channel(None);
+        (CancelTrigger { grace_end: tx }, CancelToken { grace_end: rx })
+    }
+
+    /// a token that is never cancelled
+    pub fn never() -> CancelToken {
+        CancelToken::channel().1
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.grace_end.borrow().is_some()
+    }
+
+    /// resolves once the grace period after cancellation is over
+    pub async fn grace_over(&self) {
+        let mut grace_end = self.grace_end.clone();
+        loop {
+            let end = *grace_end.borrow();
+            if let Some(end) = end {
+                sleep(end.saturating_duration_since(Instant::now())).await;
+                return;
+            }
+            if grace_end.changed().await.is_err() {
+                pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl CancelTrigger {
+    /// stop starting new cases, running cases get `grace` to finish
+    pub fn cancel(&self, grace: Duration) {
+        let _ = self.grace_end.send(Some(Instant::now() + grace));
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn cancel_grace() {
+    let (trigger, token) = CancelToken::channel();
+    assert!(!token.is_cancelled());
+    let waiting = token.clone();
+    let grace_over = tokio::spawn(async move {
+        waiting.grace_over().await;
+        Instant::now()
+    });
+
+    let cancel_at = Instant::now();
+    trigger.cancel(Duration::from_millis(50));
+    assert!(token.is_cancelled());
+    let grace_over_at = grace_over.await.unwrap();
+    assert!(grace_over_at - cancel_at >= Duration::from_millis(50));
+
+    let never = CancelToken::never();
+    assert!(!never.is_cancelled());
+    let grace_over = tokio::time::timeout(Duration::from_millis(50), never.grace_over()).await;
+    assert!(grace_over.is_err());
+}
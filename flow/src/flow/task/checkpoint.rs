@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use chord_core::flow::AbortScope;
+use chord_core::future::fs::{create_dir_all, read, remove_dir, remove_dir_all, rename, write};
+use chord_core::future::path::exists;
+use chord_core::task::StageState;
+use chord_core::value::{from_slice, json, to_string_pretty, Map, Value};
+
+pub type Error = Box<dyn std::error::Error + Sync + Send>;
+
+/// checkpoints of all tasks of one execution, a dir per task
+pub struct JobCheckpoint {
+    dir: PathBuf,
+    resume: bool,
+    pre: bool,
+}
+
+impl JobCheckpoint {
+    /// `resume` keeps the existing checkpoints of the execution, otherwise they start over,
+    /// `pre` saves the context of the task `pre` as well, it is kept in plain text
+    pub fn new(dir: PathBuf, resume: bool, pre: bool) -> JobCheckpoint {
+        JobCheckpoint { dir, resume, pre }
+    }
+
+    pub async fn task(&self, task_id: &str) -> Result<Checkpoint, Error> {
+        let dir = self.dir.join(task_id);
+        if !self.resume && exists(dir.as_path()).await {
+            remove_dir_all(dir.as_path()).await?;
+        }
+        create_dir_all(dir.join("stage")).await?;
+        Ok(Checkpoint { dir, pre: self.pre })
+    }
+
+    /// drops the checkpoint of a task which ended well, and the dir of the execution once empty
+    pub async fn task_remove(&self, task_id: &str) -> Result<(), Error> {
+        let dir = self.dir.join(task_id);
+        if exists(dir.as_path()).await {
+            remove_dir_all(dir.as_path()).await?;
+        }
+        let _ = remove_dir(self.dir.as_path()).await;
+        Ok(())
+    }
+}
+
+/// progress of a task:
+/// the `pre` context if asked for, and a file per stage
+pub struct Checkpoint {
+    dir: PathBuf,
+    pre: bool,
+}
+
+impl Checkpoint {
+    pub async fn pre(&self) -> Option<Map> {
+        if !self.pre {
+            return None;
+        }
+        let data = read(self.dir.join("pre.json")).await.ok()?;
+        from_slice::<Value>(data.as_slice()).ok()?.as_object().cloned()
+    }
+
+    pub async fn pre_save(&self, pre_ctx: &Map) -> Result<(), Error> {
+        if !self.pre {
+            return Ok(());
+        }
+        save(self.dir.join("pre.json"), &Value::Object(pre_ctx.clone())).await
+    }
+
+    /// each stage owns its file, stages running in parallel do not wait for each other
+    pub async fn stage(&self, stage_id: &str) -> Result<StageCheckpoint, Error> {
+        let path = self.dir.join("stage").join(format!("{}.json", stage_id));
+        let data = if exists(path.as_path()).await {
+            from_slice(read(path.as_path()).await?.as_slice())?
+        } else {
+            json!({})
+        };
+        Ok(StageCheckpoint { path, data })
+    }
+}
+
+/// state of a finished stage,
+/// or the round, the reported case offset and the cases finished past it of the running stage
+pub struct StageCheckpoint {
+    path: PathBuf,
+    data: Value,
+}
+
+impl StageCheckpoint {
+    pub fn state(&self) -> Option<StageState> {
+        let cause = self.data["cause"].as_str().unwrap_or("").to_string();
+        match self.data["state"].as_str()? {
+            "O" => Some(StageState::Ok),
+            "F" => Some(StageState::Fail(cause)),
+            "T" => Some(StageState::Timeout(cause)),
+            _ => None,
+        }
+    }
+
+    pub fn aborted(&self) -> Option<AbortScope> {
+        match self.data["abort"].as_str()? {
+            "stage" => Some(AbortScope::Stage),
+            "task" => Some(AbortScope::Task),
+            _ => None,
+        }
+    }
+
+    /// round, count of leading cases reported in that round, and seq of the cases reported after them
+    pub fn progress(&self) -> Option<(usize, usize, BTreeSet<usize>)> {
+        let round = self.data["round"].as_u64()? as usize;
+        let case = self.data["case"].as_u64().unwrap_or(0) as usize;
+        let done = self.data["done"]
+            .as_array()
+            .map(|d| d.iter().filter_map(|s| s.as_u64()).map(|s| s as usize).collect())
+            .unwrap_or_default();
+        Some((round, case, done))
+    }
+
+    /// the file is only written when the progress moved
+    pub async fn progress_save(
+        &mut self,
+        round: usize,
+        case: usize,
+        done: &BTreeSet<usize>,
+    ) -> Result<(), Error> {
+        let data = json!({
+            "round": round,
+            "case": case,
+            "done": done,
+        });
+        if data == self.data {
+            return Ok(());
+        }
+        self.data = data;
+        save(self.path.clone(), &self.data).await
+    }
+
+    /// only stages which ran to the end are saved, others run again on resume
+    pub async fn state_save(
+        &mut self,
+        state: &StageState,
+        aborted: Option<AbortScope>,
+    ) -> Result<(), Error> {
+        let (state, cause) = match state {
+            StageState::Ok => ("O", ""),
+            StageState::Fail(c) => ("F", c.as_str()),
+            StageState::Timeout(c) => ("T", c.as_str()),
+            StageState::Err(_) | StageState::Cancelled(_) => return Ok(()),
+        };
+        self.data = json!({
+            "state": state,
+            "cause": cause,
+        });
+        if let Some(aborted) = aborted {
+            self.data["abort"] = match aborted {
+                AbortScope::Stage => "stage",
+                AbortScope::Task => "task",
+            }
+            .into();
+        }
+        save(self.path.clone(), &self.data).await
+    }
+}
+
+async fn save(path: PathBuf, data: &Value) -> Result<(), Error> {
+    let path_tmp = path.with_extension("json.tmp");
+    write(path_tmp.as_path(), to_string_pretty(data)?).await?;
+    rename(path_tmp, path.as_path()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn checkpoint_resume() {
+    let dir = std::env::temp_dir().join(format!("chord-checkpoint-{}", std::process::id()));
+    let mut pre_ctx = Map::new();
+    pre_ctx.insert("token".into(), json!("secret"));
+
+    let job = JobCheckpoint::new(dir.clone(), false, false);
+    let task = job.task("t").await.unwrap();
+    task.pre_save(&pre_ctx).await.unwrap();
+    let mut stage = task.stage("s").await.unwrap();
+    stage
+        .progress_save(2, 3, &BTreeSet::from([5, 7]))
+        .await
+        .unwrap();
+    assert!(!exists(dir.join("t").join("pre.json")).await);
+
+    // a resumed job sees the progress, the pre context only when asked for
+    let job = JobCheckpoint::new(dir.clone(), true, true);
+    let task = job.task("t").await.unwrap();
+    assert_eq!(task.pre().await, None);
+    task.pre_save(&pre_ctx).await.unwrap();
+    assert_eq!(task.pre().await, Some(pre_ctx));
+    let mut stage = task.stage("s").await.unwrap();
+    assert_eq!(stage.progress(), Some((2, 3, BTreeSet::from([5, 7]))));
+    assert!(stage.state().is_none());
+    stage
+        .state_save(&StageState::Fail("abort".into()), Some(AbortScope::Stage))
+        .await
+        .unwrap();
+    let stage = task.stage("s").await.unwrap();
+    assert!(matches!(stage.state(), Some(StageState::Fail(c)) if c == "abort"));
+    assert_eq!(stage.aborted(), Some(AbortScope::Stage));
+
+    // a job which does not resume starts over
+    let job = JobCheckpoint::new(dir.clone(), false, false);
+    let task = job.task("t").await.unwrap();
+    assert!(task.stage("s").await.unwrap().progress().is_none());
+
+    job.task_remove("t").await.unwrap();
+    assert!(!exists(dir.as_path()).await);
+}
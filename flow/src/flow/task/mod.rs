@@ -1,36 +1,49 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error as StdError;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use futures::future::join_all;
+use futures::stream::{FuturesUnordered, StreamExt};
 use handlebars::RenderError;
 use log::{error, info, trace, warn};
 use tracing::{error_span, Instrument};
 
 use chord_core::case::{CaseAsset, CaseState};
 use chord_core::collection::TailDropVec;
-use chord_core::flow::Flow;
-use chord_core::future::time::timeout;
+use chord_core::flow::{AbortScope, Flow, Ramp};
 use chord_core::input::{StageLoader, TaskLoader};
-use chord_core::output::{StageReporter, TaskReporter};
+use chord_core::output::TaskReporter;
 use chord_core::output::Utc;
+use chord_core::stat::Statistic;
 use chord_core::step::{StepAsset, StepState};
-use chord_core::task::{StageAsset, StageId, StageState, TaskAsset, TaskId, TaskState};
+use chord_core::task::{StageState, TaskAsset, TaskId, TaskState};
 use chord_core::value::{json, Map, Value};
 use res::TaskAssetStruct;
 
 use crate::CTX_ID;
 use crate::flow::assign_by_render;
 use crate::flow::case;
-use crate::flow::case::arg::{CaseArgStruct, CaseIdStruct};
+use crate::flow::case::arg::{CaseArgStruct, CaseIdStruct, CaseOption};
+use crate::flow::case::res::CaseAssetStruct;
 use crate::flow::step::{action_asset_to_value, StepRunner};
 use crate::flow::step::arg::{ArgStruct, ChordStruct};
 use crate::flow::task::arg::{StageIdStruct, TaskIdStruct};
+use crate::flow::task::cancel::CancelToken;
+use crate::flow::task::checkpoint::Checkpoint;
 use crate::flow::task::Error::*;
-use crate::flow::task::res::StageAssetStruct;
+use crate::flow::task::stage::StageRunner;
+use crate::flow::template;
 use crate::model::app::{App, RenderContext};
 
 pub mod arg;
+pub mod cancel;
+pub mod checkpoint;
 pub mod res;
+mod stage;
+
+pub(crate) static DURATION_EXCEEDED: &str = "duration exceeded";
+pub(crate) static CANCELLED: &str = "cancelled";
+static RAMP_TICK: Duration = Duration::from_millis(100);
 
 #[derive(thiserror::Error, Debug)]
 enum Error {
@@ -61,11 +74,6 @@ enum Error {
 
 #[derive()]
 pub struct TaskRunner {
-    step_vec: Arc<TailDropVec<(String, StepRunner)>>,
-    stage_round_no: usize,
-    stage_id: Arc<String>,
-    stage_state: StageState,
-
     pre_ctx: Option<Arc<Map>>,
     #[allow(dead_code)]
     pre_asset: Option<Box<dyn CaseAsset>>,
@@ -73,8 +81,12 @@ pub struct TaskRunner {
     pre_step_vec: Option<Arc<TailDropVec<(String, StepRunner)>>>,
 
     task_state: TaskState,
+    task_stat: Statistic,
 
     def_ctx: Option<Arc<Map>>,
+    cancel: CancelToken,
+    checkpoint: Option<Checkpoint>,
+    dry_run: Option<usize>,
     reporter: Box<dyn TaskReporter>,
     loader: Box<dyn TaskLoader>,
     chord: Arc<ChordStruct>,
@@ -90,21 +102,21 @@ impl TaskRunner {
         app: Arc<dyn App>,
         flow: Arc<Flow>,
         id: Arc<TaskIdStruct>,
+        cancel: CancelToken,
+        checkpoint: Option<Checkpoint>,
     ) -> TaskRunner {
         let runner = TaskRunner {
-            step_vec: Arc::new(TailDropVec::from(vec![])),
-            stage_id: Arc::new("0".into()),
-            stage_round_no: 0,
-
-            stage_state: StageState::Ok,
-
             pre_ctx: None,
             pre_asset: None,
             pre_step_vec: None,
 
             task_state: TaskState::Ok,
+            task_stat: Statistic::new(Utc::now()),
 
             def_ctx: None,
+            cancel,
+            checkpoint,
+            dry_run: None,
             reporter,
             loader,
             chord: Arc::new(ChordStruct::new(app.clone())),
@@ -116,6 +128,12 @@ impl TaskRunner {
         runner
     }
 
+    /// explain the actions of the first `case_count` cases of each stage instead of executing them
+    pub fn dry_run(mut self, case_count: usize) -> TaskRunner {
+        self.dry_run = Some(case_count);
+        self
+    }
+
     pub fn id(&self) -> Arc<dyn TaskId> {
         self.id.clone()
     }
@@ -130,6 +148,7 @@ impl TaskRunner {
     async fn run0(mut self) -> Box<dyn TaskAsset> {
         trace!("task run");
         let start = Utc::now();
+        self.task_stat = Statistic::new(start);
 
         if let Err(e) = self.reporter.start(start).await {
             error!("task Err");
@@ -142,6 +161,7 @@ impl TaskRunner {
                     self.id.task().to_string(),
                     e,
                 ))),
+                Statistic::new(start),
             ));
         }
 
@@ -158,89 +178,28 @@ impl TaskRunner {
                     start,
                     Utc::now(),
                     TaskState::Err(Box::new(Render("def".to_string(), e))),
+                    Statistic::new(start),
                 ));
             } else {
                 self.def_ctx = Some(Arc::new(rso.unwrap()));
             }
         }
 
-        if let Some(pre_step_id_vec) = self.flow.pre_step_id_vec() {
-            if !pre_step_id_vec.is_empty() {
-                let pre_step_vec = step_vec_create(
-                    self.app.as_ref(),
-                    self.flow.as_ref(),
-                    pre_step_id_vec.into_iter().map(|s| s.to_owned()).collect(),
-                    Arc::new(StageIdStruct::new(
-                        self.id.clone(),
-                        "init".to_string(),
-                        0.to_string(),
-                    )),
-                    self.chord.clone(),
-                )
-                    .await;
-                if let Err(e) = pre_step_vec {
-                    error!("task Err");
-                    return Box::new(TaskAssetStruct::new(
-                        self.id.clone(),
-                        start,
-                        Utc::now(),
-                        TaskState::Err(Box::new(e)),
-                    ));
-                }
-
-                let pre_step_vec = Arc::new(TailDropVec::from(pre_step_vec.unwrap()));
-                let pre_arg = pre_arg(
-                    self.flow.clone(),
-                    self.id.clone(),
-                    self.def_ctx.clone(),
-                    pre_step_vec.clone(),
-                )
-                    .await;
-                if let Err(e) = pre_arg {
-                    error!("task Err");
-                    return Box::new(TaskAssetStruct::new(
-                        self.id,
-                        start,
-                        Utc::now(),
-                        TaskState::Err(Box::new(e)),
-                    ));
-                }
-
-                let pre_asset = case_run(self.app.as_ref(), pre_arg.unwrap()).await;
-
-                match pre_asset.state() {
-                    CaseState::Err(_) => {
-                        error!("task Err");
-                        return Box::new(TaskAssetStruct::new(
-                            self.id,
-                            start,
-                            Utc::now(),
-                            TaskState::Err(Box::new(PreErr)),
-                        ));
-                    }
+        // advisory, a reference the check cannot follow may still render,
+        // the case fields are left unknown so that no loader is opened for it
+        for e in template::check(self.flow.as_ref(), &HashMap::new()) {
+            warn!("template {}", e);
+        }
 
-                    CaseState::Fail(v) => {
-                        error!("task Err");
-                        return Box::new(TaskAssetStruct::new(
-                            self.id.clone(),
-                            start,
-                            Utc::now(),
-                            TaskState::Err(Box::new(PreFail(
-                                v.last().unwrap().id().step().to_string(),
-                            ))),
-                        ));
-                    }
-                    CaseState::Ok(sa_vec) => {
-                        let pre_ctx = pre_ctx_create(sa_vec.as_ref()).await;
-                        self.pre_ctx = Some(Arc::new(pre_ctx));
-                        self.pre_asset = Some(pre_asset);
-                        self.pre_step_vec = Some(pre_step_vec);
-                    }
-                }
-            }
+        // post runs whenever pre was tried, to clean up what a failed pre left behind
+        let result = match self.pre_run().await {
+            Ok(()) => self.task_run().await,
+            Err(e) => Err(e),
         };
-
-        let result = self.task_run().await;
+        self.task_stat.end_at(Utc::now());
+        if self.cancel.is_cancelled() && result.is_ok() {
+            self.task_state = TaskState::Cancelled(CANCELLED.to_string());
+        }
 
         let task_asset = if let Err(e) = result {
             error!("task Err");
@@ -249,12 +208,19 @@ impl TaskRunner {
                 start,
                 Utc::now(),
                 TaskState::Err(Box::new(e)),
+                self.task_stat.clone(),
             )
         } else {
-            match self.task_state {
+            match std::mem::replace(&mut self.task_state, TaskState::Ok) {
                 TaskState::Ok => {
                     info!("task Ok");
-                    TaskAssetStruct::new(self.id.clone(), start, Utc::now(), TaskState::Ok)
+                    TaskAssetStruct::new(
+                        self.id.clone(),
+                        start,
+                        Utc::now(),
+                        TaskState::Ok,
+                        self.task_stat.clone(),
+                    )
                 }
                 TaskState::Fail(c) => {
                     warn!("task Fail");
@@ -263,15 +229,34 @@ impl TaskRunner {
                         start,
                         Utc::now(),
                         TaskState::Fail(c.clone()),
+                        self.task_stat.clone(),
+                    )
+                }
+                TaskState::Cancelled(c) => {
+                    warn!("task Cancelled");
+                    TaskAssetStruct::new(
+                        self.id.clone(),
+                        start,
+                        Utc::now(),
+                        TaskState::Cancelled(c.clone()),
+                        self.task_stat.clone(),
                     )
                 }
                 TaskState::Err(e) => {
                     error!("task Err");
-                    TaskAssetStruct::new(self.id.clone(), start, Utc::now(), TaskState::Err(e))
+                    TaskAssetStruct::new(
+                        self.id.clone(),
+                        start,
+                        Utc::now(),
+                        TaskState::Err(e),
+                        self.task_stat.clone(),
+                    )
                 }
             }
         };
 
+        self.post_run(task_asset.state()).await;
+
         if let Err(e) = self.reporter.end(&task_asset).await {
             error!("task Err");
             return Box::new(TaskAssetStruct::new(
@@ -283,246 +268,204 @@ impl TaskRunner {
                     self.id.task().to_string(),
                     e,
                 ))),
+                task_asset.stat().clone(),
             ));
         }
 
         Box::new(task_asset)
     }
 
-    async fn task_run(&mut self) -> Result<(), Error> {
-        let stage_id_vec: Vec<String> = self
-            .flow
-            .stage_id_vec()
-            .into_iter()
-            .map(|s| s.to_owned())
-            .collect();
-        for state_id in stage_id_vec {
-            trace!("stage run");
-            self.stage_run(state_id.as_str())
-                .await?;
-            if let StageState::Fail(_) = self.stage_state {
-                if "stage_fail" == self.flow.stage_break_on(state_id.as_str()) {
-                    break;
-                }
-            }
+    /// runs the `pre` steps, or resumes their context from the checkpoint
+    async fn pre_run(&mut self) -> Result<(), Error> {
+        let pre_resumed = match self.checkpoint.as_ref() {
+            Some(checkpoint) => checkpoint.pre().await,
+            None => None,
+        };
+        if let Some(pre_ctx) = pre_resumed {
+            trace!("task pre resume");
+            self.pre_ctx = Some(Arc::new(pre_ctx));
+            return Ok(());
         }
-        Ok(())
-    }
-
-    async fn stage_run(&mut self, stage_id: &str) -> Result<(), Error> {
-        self.stage_id = Arc::new(stage_id.to_string());
-        self.stage_state = StageState::Ok;
-        let step_id_vec: Vec<String> = self
-            .flow
-            .stage_step_id_vec(stage_id)
-            .into_iter()
-            .map(|s| s.to_owned())
-            .collect();
-        let action_vec = step_vec_create(
+        let pre_step_id_vec = match self.flow.pre_step_id_vec() {
+            Some(pre_step_id_vec) if !pre_step_id_vec.is_empty() => pre_step_id_vec,
+            _ => return Ok(()),
+        };
+        let pre_step_vec = step_vec_create(
             self.app.as_ref(),
             self.flow.as_ref(),
-            step_id_vec,
+            pre_step_id_vec.into_iter().map(|s| s.to_owned()).collect(),
             Arc::new(StageIdStruct::new(
                 self.id.clone(),
-                stage_id.to_string(),
-                "0".to_string())),
+                "init".to_string(),
+                0.to_string(),
+            )),
             self.chord.clone(),
+            self.dry_run.is_some(),
         )
             .await?;
-        self.step_vec = Arc::new(TailDropVec::from(action_vec));
-
-        let duration = self.flow.stage_duration(stage_id);
 
-        let srr = self.stage_run_round(stage_id);
-        match timeout(duration, srr).await {
-            Ok(r) => r?,
-            Err(_) => (),
-        }
-        return Ok(());
-    }
+        let pre_step_vec = Arc::new(TailDropVec::from(pre_step_vec));
+        let pre_arg = pre_arg(
+            self.flow.clone(),
+            self.id.clone(),
+            self.def_ctx.clone(),
+            pre_step_vec.clone(),
+        )
+            .await?;
 
-    async fn stage_run_round(&mut self, stage_id: &str) -> Result<(), Error> {
-        let concurrency = self.flow.stage_concurrency(stage_id);
-        let round_max = self.flow.stage_round(stage_id);
-        let mut round_count = 1;
-        loop {
-            self.stage_round_no = round_count;
-            let stage = Arc::new(StageIdStruct::new(
-                self.id.clone(),
-                stage_id.to_string(),
-                round_count.to_string(),
-            ));
-            self.stage_run_once(stage, concurrency)
-                .instrument(error_span!("stage", stage=format!("{}-{}", stage_id, round_count)))
-                .await?;
-            if round_count >= round_max {
-                break;
+        let pre_asset = case_run(self.app.as_ref(), pre_arg).await;
+        match pre_asset.state() {
+            CaseState::Err(_) | CaseState::Cancelled(_) => Err(PreErr),
+            CaseState::Fail(v) => Err(PreFail(v.last().unwrap().id().step().to_string())),
+            CaseState::Ok(sa_vec) => {
+                let pre_ctx = pre_ctx_create(sa_vec.as_ref()).await;
+                if let Some(checkpoint) = self.checkpoint.as_ref() {
+                    if let Err(e) = checkpoint.pre_save(&pre_ctx).await {
+                        warn!("checkpoint Err, {}", e);
+                    }
+                }
+                self.pre_ctx = Some(Arc::new(pre_ctx));
+                self.pre_asset = Some(pre_asset);
+                self.pre_step_vec = Some(pre_step_vec);
+                Ok(())
             }
-            round_count += 1;
         }
-        return Ok(());
     }
 
-    async fn stage_run_once(&mut self, stage: Arc<StageIdStruct>, concurrency: usize) -> Result<(), Error> {
-        trace!("stage run");
+    /// runs the `post` steps once the stages are over, whatever they ended in,
+    /// a `post` failure is reported apart and leaves the task state as it is
+    async fn post_run(&mut self, task_state: &TaskState) {
+        let post_step_id_vec = match self.flow.post_step_id_vec() {
+            Some(post_step_id_vec) => post_step_id_vec,
+            None => return,
+        };
+        trace!("task post");
         let start = Utc::now();
-
-        let mut loader = self
-            .loader
-            .stage(stage.stage())
-            .await
-            .map_err(|e| Loader("stage".to_string(), stage.to_string(), e))?;
-
-        let mut reporter = self
-            .reporter
-            .stage(stage.stage())
-            .await
-            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
-
-        reporter
-            .start(Utc::now())
-            .await
-            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
-
-        let result = self
-            .stage_run_io(stage.clone(), loader.as_mut(), reporter.as_mut(), concurrency)
+        let stage = Arc::new(StageIdStruct::new(
+            self.id.clone(),
+            "post".to_string(),
+            0.to_string(),
+        ));
+        let post_step_vec = step_vec_create(
+            self.app.as_ref(),
+            self.flow.as_ref(),
+            post_step_id_vec.into_iter().map(|s| s.to_owned()).collect(),
+            stage.clone(),
+            self.chord.clone(),
+            self.dry_run.is_some(),
+        )
             .await;
 
-        let stage_asset = if let Err(e) = result {
-            error!("stage Err, {:?}", e);
-            StageAssetStruct::new(
-                stage.clone(),
+        let post_asset: Box<dyn CaseAsset> = match post_step_vec {
+            Ok(post_step_vec) => {
+                let mut post_arg = CaseArgStruct::new(
+                    self.flow.clone(),
+                    Arc::new(TailDropVec::from(post_step_vec)),
+                    Value::Null,
+                    stage,
+                    "post".into(),
+                    CaseOption {
+                        pre_ctx: self.pre_ctx.clone(),
+                        def_ctx: self.def_ctx.clone(),
+                        timeout: None,
+                    },
+                );
+                post_arg.task_register(task_ctx_create(task_state));
+                case_run(self.app.as_ref(), post_arg).await
+            }
+            Err(e) => Box::new(CaseAssetStruct::new(
+                Arc::new(CaseIdStruct::new(stage, "post".into())),
                 start,
                 Utc::now(),
-                StageState::Err(Box::new(e)),
-            )
-        } else {
-            match &self.stage_state {
-                StageState::Ok => {
-                    info!("stage Ok");
-                    StageAssetStruct::new(stage.clone(), start, Utc::now(), StageState::Ok)
-                }
-                StageState::Fail(c) => {
-                    warn!("stage Fail");
-                    StageAssetStruct::new(
-                        stage.clone(),
-                        start,
-                        Utc::now(),
-                        StageState::Fail(c.clone()),
-                    )
-                }
-                StageState::Err(_) => unreachable!(),
-            }
+                Value::Null,
+                CaseState::Err(Box::new(Box::new(e))),
+            )),
         };
+        match post_asset.state() {
+            CaseState::Ok(_) => info!("post Ok"),
+            CaseState::Fail(_) => warn!("post Fail"),
+            CaseState::Err(_) | CaseState::Cancelled(_) => error!("post Err"),
+        }
+        if let Err(e) = self.reporter.post(post_asset.as_ref()).await {
+            error!("post reporter Err, {}", e);
+        }
+    }
 
-        reporter
-            .end(&stage_asset)
-            .await
-            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
-
-        match stage_asset.state() {
-            StageState::Ok => Ok(()),
-            StageState::Fail(_) => Ok(()),
-            StageState::Err(e) => Err(Unknown(e.to_string())),
+    /// runs the stages, a stage failure is kept in `task_state`
+    async fn task_run(&mut self) -> Result<(), Error> {
+        let mut task_stat = self.task_stat.clone();
+        let (result, task_fail) = self.stage_graph_run(&mut task_stat).await;
+        self.task_stat = task_stat;
+        if let Some(cause) = task_fail {
+            self.task_state = TaskState::Fail(cause);
         }
+        result
     }
 
-    async fn stage_run_io(
-        &mut self,
-        stage: Arc<StageIdStruct>,
-        loader: &mut dyn StageLoader,
-        reporter: &mut dyn StageReporter,
-        concurrency: usize,
-    ) -> Result<(), Error> {
-        let mut load_times = 0;
+    /// starts each stage once all the stages it depends on are done, so stages ready at the same time run in parallel.
+    /// gives the first stage error and the cause of the last failed stage
+    async fn stage_graph_run(
+        &self,
+        task_stat: &mut Statistic,
+    ) -> (Result<(), Error>, Option<String>) {
+        let mut stage_pending = self.flow.stage_id_vec();
+        let mut stage_done: HashSet<&str> = HashSet::new();
+        let mut stage_running = FuturesUnordered::new();
+        let mut result = Ok(());
+        let mut task_fail = None;
+        let mut stopped = false;
         loop {
-            let case_data_vec: Vec<(String, Value)> = loader
-                .load(concurrency)
-                .await
-                .map_err(|e| Loader("stage".to_string(), stage.to_string(), e))?;
-            load_times = load_times + 1;
-            if case_data_vec.len() == 0 {
-                return if load_times == 1 {
-                    Err(CaseEmpty(stage.to_string()))
-                } else {
-                    trace!("stage exhaust data");
-                    Ok(())
+            while !stopped && !self.cancel.is_cancelled() {
+                let ready = stage_pending.iter().position(|stage_id| {
+                    self.flow
+                        .stage_depends_on(stage_id)
+                        .iter()
+                        .all(|d| stage_done.contains(d))
+                });
+                let stage_id = match ready {
+                    Some(idx) => stage_pending.remove(idx),
+                    None => break,
                 };
-            }
 
-            trace!(
-                "stage load data, {}",
-                case_data_vec.len()
-            );
-
-            let case_asset_vec = self.case_data_vec_run(stage.clone(), case_data_vec, concurrency).await;
-            let first_fail = case_asset_vec.iter().find(|ca| !ca.state().is_ok());
-            if first_fail.is_some() {
-                let cause_case = first_fail.unwrap();
-                let cause = match cause_case.state() {
-                    CaseState::Err(_) => format!("case: {}", cause_case.id()),
-                    CaseState::Fail(v) => {
-                        let last_step_id = v
-                            .last()
-                            .map(|s| s.id().to_string())
-                            .or_else(|| Some(String::new()))
-                            .unwrap();
-                        format!("step: {}", last_step_id)
+                let mut stage_runner = StageRunner::new(self, stage_id);
+                stage_running.push(async move {
+                    if stage_runner.resume().await {
+                        trace!("stage resume done, {}", stage_runner.stage_id());
+                    } else {
+                        stage_runner.run().await?;
                     }
-                    CaseState::Ok(_) => String::new(),
-                };
-                self.stage_state = StageState::Fail(cause.clone());
-                self.task_state = TaskState::Fail(cause.clone());
+                    Ok::<_, Error>(stage_runner)
+                });
             }
-            reporter
-                .report(&case_asset_vec)
-                .await
-                .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
-        }
-    }
 
-    async fn case_data_vec_run(
-        &mut self,
-        stage: Arc<StageIdStruct>,
-        case_vec: Vec<(String, Value)>,
-        concurrency: usize,
-    ) -> Vec<Box<dyn CaseAsset>> {
-        let ca_vec = self.case_arg_vec(stage, case_vec);
-
-        let mut case_asset_vec = Vec::<Box<dyn CaseAsset>>::new();
-        let mut futures = vec![];
-        for ca in ca_vec {
-            let f = case_run_arc(self.app.clone(), ca);
-            futures.push(f);
-            if futures.len() >= concurrency {
-                let case_asset = join_all(futures.split_off(0)).await;
-                case_asset_vec.extend(case_asset);
+            let stage_runner = match stage_running.next().await {
+                Some(Ok(stage_runner)) => stage_runner,
+                Some(Err(e)) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                    stopped = true;
+                    continue;
+                }
+                None => break,
+            };
+            let stage_id = stage_runner.stage_id();
+            task_stat.merge(stage_runner.stat());
+            // a stage out of its duration fails the task as well
+            if let StageState::Fail(c) | StageState::Timeout(c) = stage_runner.state() {
+                task_fail = Some(c.clone());
+            }
+            stage_done.insert(stage_id);
+            if stage_break(
+                self.flow.as_ref(),
+                stage_id,
+                stage_runner.state(),
+                stage_runner.aborted(),
+            ) {
+                stopped = true;
             }
         }
-        if !futures.is_empty() {
-            let case_asset = join_all(futures).await;
-            case_asset_vec.extend(case_asset);
-        }
-
-        case_asset_vec
-    }
-
-    fn case_arg_vec<'p>(&self, stage: Arc<StageIdStruct>, data: Vec<(String, Value)>) -> Vec<CaseArgStruct> {
-        let vec = data
-            .into_iter()
-            .map(|(id, d)| {
-                CaseArgStruct::new(
-                    self.flow.clone(),
-                    self.step_vec.clone(),
-                    d,
-                    self.pre_ctx.clone(),
-                    self.def_ctx.clone(),
-                    stage.clone(),
-                    id,
-                )
-            })
-            .collect();
-        return vec;
+        (result, task_fail)
     }
 }
 
@@ -541,10 +484,12 @@ async fn pre_arg(
         flow.clone(),
         pre_step_vec,
         Value::Null,
-        None,
-        def_ctx,
         stage,
         "pre".into(),
+        CaseOption {
+            def_ctx,
+            ..CaseOption::default()
+        },
     ))
 }
 
@@ -563,6 +508,16 @@ async fn pre_ctx_create(sa_vec: &Vec<Box<dyn StepAsset>>) -> Map {
     pre_ctx
 }
 
+/// `task` of the `post` steps, the state of the ended task in the form of the report files
+fn task_ctx_create(task_state: &TaskState) -> Value {
+    match task_state {
+        TaskState::Ok => json!({ "state": "O" }),
+        TaskState::Fail(c) => json!({ "state": "F", "cause": c }),
+        TaskState::Err(e) => json!({ "state": "E", "cause": e.to_string() }),
+        TaskState::Cancelled(c) => json!({ "state": "C", "cause": c }),
+    }
+}
+
 
 async fn step_vec_create(
     app: &dyn App,
@@ -570,6 +525,7 @@ async fn step_vec_create(
     step_id_vec: Vec<String>,
     stage: Arc<StageIdStruct>,
     chord: Arc<ChordStruct>,
+    dry_run: bool,
 ) -> Result<Vec<(String, StepRunner)>, Error> {
     let mut step_vec = vec![];
     let fake_case_id = Arc::new(CaseIdStruct::new(
@@ -586,7 +542,7 @@ async fn step_vec_create(
             sid.clone(),
         );
 
-        let pr = StepRunner::new(chord.clone(), &mut arg)
+        let pr = StepRunner::new(chord.clone(), &mut arg, dry_run)
             .await
             .map_err(|e| Step(sid.clone(), Box::new(e)))?;
         step_vec.push((sid, pr));
@@ -601,11 +557,321 @@ async fn case_run(flow_ctx: &dyn App, case_arg: CaseArgStruct) -> Box<dyn CaseAs
 }
 
 
-async fn case_run_arc(flow_ctx: Arc<dyn App>, case_arg: CaseArgStruct) -> Box<dyn CaseAsset> {
-    CTX_ID
-        .scope(
-            case_arg.id().to_string(),
-            case_run(flow_ctx.as_ref(), case_arg),
-        )
-        .await
+async fn case_run_arc(
+    flow_ctx: Arc<dyn App>,
+    case_arg: CaseArgStruct,
+    deadline: Instant,
+    cancel: CancelToken,
+) -> Box<dyn CaseAsset> {
+    let id = case_arg.id();
+    let run = case::run_until(flow_ctx.as_ref(), case_arg, deadline, cancel);
+    Box::new(CTX_ID.scope(id.to_string(), run).await)
+}
+
+async fn case_data_skip(
+    stage: &StageIdStruct,
+    loader: &mut dyn StageLoader,
+    mut case_skip: usize,
+) -> Result<(), Error> {
+    while case_skip > 0 {
+        let case_data_vec = loader
+            .load(case_skip)
+            .await
+            .map_err(|e| Loader("stage".to_string(), stage.to_string(), e))?;
+        if case_data_vec.is_empty() {
+            break;
+        }
+        case_skip = case_skip.saturating_sub(case_data_vec.len());
+    }
+    Ok(())
+}
+
+async fn case_data_next(
+    stage: &StageIdStruct,
+    loader: &mut dyn StageLoader,
+    case_data_queue: &mut VecDeque<(String, Value)>,
+    load_times: &mut usize,
+    size: usize,
+) -> Result<Option<(String, Value)>, Error> {
+    if case_data_queue.is_empty() {
+        let case_data_vec: Vec<(String, Value)> = loader
+            .load(size)
+            .await
+            .map_err(|e| Loader("stage".to_string(), stage.to_string(), e))?;
+        *load_times += 1;
+        if case_data_vec.is_empty() {
+            if *load_times == 1 {
+                return Err(CaseEmpty(stage.to_string()));
+            }
+            trace!("stage exhaust data");
+            return Ok(None);
+        }
+
+        trace!("stage load data, {}", case_data_vec.len());
+        case_data_queue.extend(case_data_vec);
+    }
+    Ok(case_data_queue.pop_front())
+}
+
+/// true if no more stage should start once `stage_id` ended in `state`,
+/// a stage aborted with scope `stage` leaves the later stages running
+fn stage_break(
+    flow: &Flow,
+    stage_id: &str,
+    state: &StageState,
+    aborted: Option<AbortScope>,
+) -> bool {
+    let break_on = flow.stage_break_on(stage_id);
+    let break_now = match state {
+        StageState::Fail(_) if aborted == Some(AbortScope::Stage) => false,
+        StageState::Fail(_) => break_on.contains(&"stage_fail"),
+        StageState::Timeout(_) => break_on.contains(&"stage_timeout"),
+        _ => false,
+    };
+    break_now || aborted == Some(AbortScope::Task)
+}
+
+/// concurrency level of the ramp at `elapsed` since stage start, and whether the ramp is over
+fn ramp_concurrency(ramp_vec: &[Ramp], elapsed: Duration) -> (usize, bool) {
+    let mut from = 0;
+    let mut start = Duration::ZERO;
+    for ramp in ramp_vec {
+        let end = start + ramp.duration();
+        if elapsed < end {
+            let progress = (elapsed - start).as_secs_f64() / ramp.duration().as_secs_f64();
+            let level = from as f64 + (ramp.to() as f64 - from as f64) * progress;
+            return (level.round() as usize, false);
+        }
+        from = ramp.to();
+        start = end;
+    }
+    (from, true)
+}
+
+#[test]
+fn ramp_level() {
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": {
+            "ramp": [
+                { "to": 10, "duration": 10 },
+                { "to": 10, "duration": 5 },
+                { "to": 0, "duration": 5 }
+            ],
+            "step": { "a": { "action": { "x": { "let": 1 } } } }
+        } }
+    });
+    let flow = Flow::new(flow, std::path::Path::new(".")).unwrap();
+    let ramp_vec = flow.stage_ramp("s").unwrap();
+
+    let level = |secs: f64| ramp_concurrency(&ramp_vec, Duration::from_secs_f64(secs));
+    assert_eq!(level(0.0), (0, false));
+    assert_eq!(level(5.0), (5, false));
+    assert_eq!(level(9.96), (10, false));
+    assert_eq!(level(10.0), (10, false));
+    assert_eq!(level(14.0), (10, false));
+    assert_eq!(level(17.5), (5, false));
+    assert_eq!(level(20.0), (0, true));
+    assert_eq!(level(60.0), (0, true));
+}
+
+#[test]
+fn stage_break_scope() {
+    let flow = |scope: &str| {
+        let flow = json!({
+            "version": "0.0.2",
+            "stage": { "s": {
+                "abort": { "consecutive": 1, "scope": scope },
+                "step": { "a": { "action": { "x": { "let": 1 } } } }
+            } }
+        });
+        Flow::new(flow, std::path::Path::new(".")).unwrap()
+    };
+    let fail = StageState::Fail("abort: 1 consecutive case fail".into());
+
+    let stage = flow("stage");
+    assert!(!stage_break(&stage, "s", &fail, Some(AbortScope::Stage)));
+    assert!(stage_break(&stage, "s", &fail, None));
+
+    let task = flow("task");
+    assert!(stage_break(&task, "s", &fail, Some(AbortScope::Task)));
+    assert!(stage_break(&task, "s", &StageState::Ok, Some(AbortScope::Task)));
+}
+
+/// a loader of `case_count` cases `{"n": <index>}` for every stage
+#[cfg(test)]
+struct RecordLoader {
+    case_count: usize,
+}
+
+#[cfg(test)]
+#[chord_core::input::async_trait]
+impl TaskLoader for RecordLoader {
+    async fn stage(
+        &self,
+        _: &str,
+    ) -> Result<Box<dyn StageLoader>, chord_core::input::Error> {
+        Ok(Box::new(RecordStageLoader {
+            next: 0,
+            case_count: self.case_count,
+        }))
+    }
+}
+
+#[cfg(test)]
+struct RecordStageLoader {
+    next: usize,
+    case_count: usize,
+}
+
+#[cfg(test)]
+#[chord_core::input::async_trait]
+impl StageLoader for RecordStageLoader {
+    async fn load(
+        &mut self,
+        size: usize,
+    ) -> Result<Vec<(String, Value)>, chord_core::input::Error> {
+        let end = self.case_count.min(self.next + size);
+        let case_vec = (self.next..end)
+            .map(|n| (n.to_string(), json!({ "n": n })))
+            .collect();
+        self.next = end;
+        Ok(case_vec)
+    }
+}
+
+/// a reporter recording the state of the `post` case and of the task, as `post O` or `end F`
+#[cfg(test)]
+struct RecordReporter {
+    event_vec: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[cfg(test)]
+#[chord_core::output::async_trait]
+impl TaskReporter for RecordReporter {
+    async fn stage(
+        &self,
+        _: &str,
+    ) -> Result<Box<dyn chord_core::output::StageReporter>, chord_core::output::Error> {
+        Ok(Box::new(RecordStageReporter {}))
+    }
+
+    async fn start(
+        &mut self,
+        _: chord_core::output::DateTime<Utc>,
+    ) -> Result<(), chord_core::output::Error> {
+        Ok(())
+    }
+
+    async fn post(&mut self, post_asset: &dyn CaseAsset) -> Result<(), chord_core::output::Error> {
+        let state = match post_asset.state() {
+            CaseState::Ok(_) => "O",
+            CaseState::Fail(_) => "F",
+            CaseState::Err(_) => "E",
+            CaseState::Cancelled(_) => "C",
+        };
+        self.event_vec.lock().unwrap().push(format!("post {}", state));
+        Ok(())
+    }
+
+    async fn end(&mut self, task_asset: &dyn TaskAsset) -> Result<(), chord_core::output::Error> {
+        let state = task_ctx_create(task_asset.state());
+        self.event_vec.lock().unwrap().push(format!("end {}", state["state"].as_str().unwrap()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+struct RecordStageReporter {}
+
+#[cfg(test)]
+#[chord_core::output::async_trait]
+impl chord_core::output::StageReporter for RecordStageReporter {
+    async fn start(
+        &mut self,
+        _: chord_core::output::DateTime<Utc>,
+    ) -> Result<(), chord_core::output::Error> {
+        Ok(())
+    }
+
+    async fn report(
+        &mut self,
+        _: &Vec<Box<dyn CaseAsset>>,
+    ) -> Result<(), chord_core::output::Error> {
+        Ok(())
+    }
+
+    async fn end(
+        &mut self,
+        _: &dyn chord_core::task::StageAsset,
+    ) -> Result<(), chord_core::output::Error> {
+        Ok(())
+    }
+}
+
+/// runs `flow` as a task over `case_count` cases per stage with the `record` action,
+/// gives the task state, the recorded args and the reported events
+#[cfg(test)]
+async fn task_record_run(flow: Value, case_count: usize) -> (String, Vec<Value>, Vec<String>) {
+    use std::sync::Mutex;
+
+    use chord_core::action::Creator;
+
+    use crate::flow::case::Record;
+
+    let value_vec = Arc::new(Mutex::new(vec![]));
+    let event_vec = Arc::new(Mutex::new(vec![]));
+    let mut creator_map: HashMap<String, Box<dyn Creator>> = HashMap::new();
+    creator_map.insert(
+        "record".into(),
+        Box::new(Record {
+            value_vec: value_vec.clone(),
+        }),
+    );
+    let app = crate::flow::app_create(creator_map).await;
+    let flow = Arc::new(Flow::new(flow, std::path::Path::new(".")).unwrap());
+    let task = TaskRunner::new(
+        Box::new(RecordLoader { case_count }),
+        Box::new(RecordReporter {
+            event_vec: event_vec.clone(),
+        }),
+        app,
+        flow,
+        Arc::new(TaskIdStruct::new("1".into(), "t".into())),
+        CancelToken::never(),
+        None,
+    );
+    let task_asset = task.run().await;
+    let state = task_ctx_create(task_asset.state())["state"].as_str().unwrap().to_string();
+    let value_vec = value_vec.lock().unwrap().clone();
+    let event_vec = event_vec.lock().unwrap().clone();
+    (state, value_vec, event_vec)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn post_always() {
+    let flow = |pre: &str, stage: &str| {
+        json!({
+            "version": "0.0.2",
+            "pre": { "step": { "p": { "action": { "x": { "record": pre } } } } },
+            "stage": { "s": { "step": { "a": { "action": { "x": { "record": stage } } } } } },
+            "post": { "step": { "q": { "action": { "x": { "record": "post {{task.state}}" } } } } }
+        })
+    };
+
+    let (state, value_vec, event_vec) = task_record_run(flow("pre", "{{case.n}}"), 2).await;
+    assert_eq!(state, "O");
+    assert_eq!(value_vec, vec![json!("pre"), json!("0"), json!("1"), json!("post O")]);
+    assert_eq!(event_vec, vec!["post O", "end O"]);
+
+    let (state, value_vec, event_vec) = task_record_run(flow("pre", "fail"), 1).await;
+    assert_eq!(state, "F");
+    assert_eq!(value_vec, vec![json!("pre"), json!("fail"), json!("post F")]);
+    assert_eq!(event_vec, vec!["post O", "end F"]);
+
+    let (state, value_vec, event_vec) = task_record_run(flow("fail", "{{case.n}}"), 1).await;
+    assert_eq!(state, "E");
+    assert_eq!(value_vec, vec![json!("fail"), json!("post E")]);
+    assert_eq!(event_vec, vec!["post O", "end E"]);
 }
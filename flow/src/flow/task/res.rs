@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 
-use chord_core::task::{StageAsset, StageId, StageState, TaskAsset, TaskId, TaskState};
+use chord_core::stat::Statistic;
+use chord_core::task::{
+    ArrivalAsset, StageAsset, StageId, StageState, TaskAsset, TaskId, TaskState,
+};
 
 use crate::flow::task::arg::{StageIdStruct, TaskIdStruct};
 
@@ -11,6 +14,7 @@ pub struct TaskAssetStruct {
     start: DateTime<Utc>,
     end: DateTime<Utc>,
     state: TaskState,
+    stat: Statistic,
 }
 
 impl TaskAssetStruct {
@@ -19,12 +23,14 @@ impl TaskAssetStruct {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
         state: TaskState,
+        stat: Statistic,
     ) -> TaskAssetStruct {
         TaskAssetStruct {
             id,
             start,
             end,
             state,
+            stat,
         }
     }
 }
@@ -45,6 +51,52 @@ impl TaskAsset for TaskAssetStruct {
     fn state(&self) -> &TaskState {
         &self.state
     }
+
+    fn stat(&self) -> &Statistic {
+        &self.stat
+    }
+}
+
+pub struct ArrivalAssetStruct {
+    rate: usize,
+    pub scheduled: usize,
+    pub started: usize,
+    pub late: usize,
+    pub skipped: usize,
+}
+
+impl ArrivalAssetStruct {
+    pub fn new(rate: usize) -> ArrivalAssetStruct {
+        ArrivalAssetStruct {
+            rate,
+            scheduled: 0,
+            started: 0,
+            late: 0,
+            skipped: 0,
+        }
+    }
+}
+
+impl ArrivalAsset for ArrivalAssetStruct {
+    fn rate(&self) -> usize {
+        self.rate
+    }
+
+    fn scheduled(&self) -> usize {
+        self.scheduled
+    }
+
+    fn started(&self) -> usize {
+        self.started
+    }
+
+    fn late(&self) -> usize {
+        self.late
+    }
+
+    fn skipped(&self) -> usize {
+        self.skipped
+    }
 }
 
 pub struct StageAssetStruct {
@@ -52,6 +104,8 @@ pub struct StageAssetStruct {
     start: DateTime<Utc>,
     end: DateTime<Utc>,
     state: StageState,
+    arrival: Option<ArrivalAssetStruct>,
+    stat: Statistic,
 }
 
 impl StageAssetStruct {
@@ -60,12 +114,16 @@ impl StageAssetStruct {
         start: DateTime<Utc>,
         end: DateTime<Utc>,
         state: StageState,
+        arrival: Option<ArrivalAssetStruct>,
+        stat: Statistic,
     ) -> StageAssetStruct {
         StageAssetStruct {
             id,
             start,
             end,
             state,
+            arrival,
+            stat,
         }
     }
 }
@@ -86,4 +144,12 @@ impl StageAsset for StageAssetStruct {
     fn state(&self) -> &StageState {
         &self.state
     }
+
+    fn arrival(&self) -> Option<&dyn ArrivalAsset> {
+        self.arrival.as_ref().map(|a| a as &dyn ArrivalAsset)
+    }
+
+    fn stat(&self) -> &Statistic {
+        &self.stat
+    }
 }
@@ -0,0 +1,984 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use log::{error, info, trace, warn};
+use tracing::{error_span, Instrument};
+
+use chord_core::case::{CaseAsset, CaseState};
+use chord_core::collection::TailDropVec;
+use chord_core::flow::{Abort, AbortScope, Arrival};
+use chord_core::future::time::{sleep, timeout};
+use chord_core::input::StageLoader;
+use chord_core::output::StageReporter;
+use chord_core::output::Utc;
+use chord_core::stat::Statistic;
+use chord_core::task::{StageAsset, StageId, StageState};
+use chord_core::value::{json, Map, Value};
+
+use crate::flow::assign_by_render;
+use crate::flow::case::arg::{CaseArgStruct, CaseOption};
+use crate::flow::step::StepRunner;
+use crate::flow::task::arg::StageIdStruct;
+use crate::flow::task::checkpoint::StageCheckpoint;
+use crate::flow::task::res::{ArrivalAssetStruct, StageAssetStruct};
+use crate::flow::task::Error::*;
+use crate::flow::task::{
+    case_data_next, case_data_skip, case_run, case_run_arc, pre_ctx_create, ramp_concurrency,
+    step_vec_create, Error, TaskRunner, CANCELLED, DURATION_EXCEEDED, RAMP_TICK,
+};
+use crate::model::app::RenderContext;
+
+/// runs the rounds of one stage, stages of a task run side by side when they do not depend on each other
+pub struct StageRunner<'t> {
+    task: &'t TaskRunner,
+    stage_id: &'t str,
+    step_vec: Arc<TailDropVec<(String, StepRunner)>>,
+    pre_step_vec: Option<Arc<TailDropVec<(String, StepRunner)>>>,
+    post_step_vec: Option<Arc<TailDropVec<(String, StepRunner)>>>,
+    /// `stage` of the cases in the current round
+    stage_ctx: Arc<Map>,
+    stage_round_no: usize,
+    stage_state: StageState,
+    stage_start: Instant,
+    stage_stat: Statistic,
+    stage_abort: Option<Abort>,
+    stage_aborted: Option<AbortScope>,
+    case_fail_window: VecDeque<bool>,
+    case_fail_consecutive: usize,
+    case_seq_next: usize,
+    case_seq_mark: usize,
+    case_seq_map: HashMap<String, usize>,
+    case_seq_done: BTreeSet<usize>,
+    checkpoint: Option<StageCheckpoint>,
+    /// all rounds of the stage merged
+    task_stat: Statistic,
+}
+
+impl<'t> StageRunner<'t> {
+    pub fn new(task: &'t TaskRunner, stage_id: &'t str) -> StageRunner<'t> {
+        StageRunner {
+            task,
+            stage_id,
+            step_vec: Arc::new(TailDropVec::from(vec![])),
+            pre_step_vec: None,
+            post_step_vec: None,
+            stage_ctx: Arc::new(Map::new()),
+            stage_round_no: 0,
+            stage_state: StageState::Ok,
+            stage_start: Instant::now(),
+            stage_stat: Statistic::new(Utc::now()),
+            stage_abort: task.flow.stage_abort(stage_id),
+            stage_aborted: None,
+            case_fail_window: VecDeque::new(),
+            case_fail_consecutive: 0,
+            case_seq_next: 0,
+            case_seq_mark: 0,
+            case_seq_map: HashMap::new(),
+            case_seq_done: BTreeSet::new(),
+            checkpoint: None,
+            task_stat: Statistic::new(Utc::now()),
+        }
+    }
+
+    pub fn stage_id(&self) -> &'t str {
+        self.stage_id
+    }
+
+    pub fn state(&self) -> &StageState {
+        &self.stage_state
+    }
+
+    pub fn aborted(&self) -> Option<AbortScope> {
+        self.stage_aborted
+    }
+
+    pub fn stat(&self) -> &Statistic {
+        &self.task_stat
+    }
+
+    pub async fn run(&mut self) -> Result<(), Error> {
+        let stage_id = self.stage_id;
+        let step_id_vec: Vec<String> = self
+            .task
+            .flow
+            .stage_step_id_vec(stage_id)
+            .into_iter()
+            .map(|s| s.to_owned())
+            .collect();
+        let action_vec = step_vec_create(
+            self.task.app.as_ref(),
+            self.task.flow.as_ref(),
+            step_id_vec,
+            Arc::new(StageIdStruct::new(
+                self.task.id.clone(),
+                stage_id.to_string(),
+                "0".to_string())),
+            self.task.chord.clone(),
+            self.task.dry_run.is_some(),
+        )
+            .await?;
+        self.step_vec = Arc::new(TailDropVec::from(action_vec));
+        self.pre_step_vec = self
+            .hook_step_vec_create(self.task.flow.stage_pre_step_id_vec(stage_id))
+            .await?;
+        self.post_step_vec = self
+            .hook_step_vec_create(self.task.flow.stage_post_step_id_vec(stage_id))
+            .await?;
+
+        self.stage_start = Instant::now();
+        let deadline = self.stage_start + self.task.flow.stage_duration(stage_id);
+        self.stage_run_round(stage_id, deadline).await?;
+        self.checkpoint_state_save().await;
+        Ok(())
+    }
+
+    async fn hook_step_vec_create(
+        &self,
+        step_id_vec: Option<Vec<&str>>,
+    ) -> Result<Option<Arc<TailDropVec<(String, StepRunner)>>>, Error> {
+        let step_id_vec = match step_id_vec {
+            Some(step_id_vec) => step_id_vec,
+            None => return Ok(None),
+        };
+        let step_vec = step_vec_create(
+            self.task.app.as_ref(),
+            self.task.flow.as_ref(),
+            step_id_vec.into_iter().map(|s| s.to_owned()).collect(),
+            Arc::new(StageIdStruct::new(
+                self.task.id.clone(),
+                self.stage_id.to_string(),
+                "0".to_string(),
+            )),
+            self.task.chord.clone(),
+            self.task.dry_run.is_some(),
+        )
+        .await?;
+        Ok(Some(Arc::new(TailDropVec::from(step_vec))))
+    }
+
+    /// takes the end of the stage from the checkpoint, true if the stage is done there
+    pub async fn resume(&mut self) -> bool {
+        let checkpoint = match self.task.checkpoint.as_ref() {
+            Some(checkpoint) => checkpoint.stage(self.stage_id).await,
+            None => return false,
+        };
+        let checkpoint = match checkpoint {
+            Ok(checkpoint) => self.checkpoint.insert(checkpoint),
+            Err(e) => {
+                warn!("checkpoint Err, {}", e);
+                return false;
+            }
+        };
+        match checkpoint.state() {
+            Some(stage_state) => {
+                self.stage_state = stage_state;
+                self.stage_aborted = checkpoint.aborted();
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn checkpoint_state_save(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            if let Err(e) = checkpoint
+                .state_save(&self.stage_state, self.stage_aborted)
+                .await
+            {
+                warn!("checkpoint Err, {}", e);
+            }
+        }
+    }
+
+    async fn stage_run_round(&mut self, stage_id: &str, deadline: Instant) -> Result<(), Error> {
+        let concurrency = self.task.flow.stage_concurrency(stage_id);
+        let round_max = match self.task.dry_run {
+            Some(_) => 1,
+            None => self.task.flow.stage_round(stage_id),
+        };
+        let progress = self.checkpoint.as_ref().and_then(|c| c.progress());
+        let (mut round_count, mut case_skip, mut case_done) =
+            progress.unwrap_or((1, 0, BTreeSet::new()));
+        loop {
+            self.stage_round_no = round_count;
+            self.case_seq_done = case_done;
+            self.checkpoint_progress_save(round_count, case_skip).await;
+            let stage = Arc::new(StageIdStruct::new(
+                self.task.id.clone(),
+                stage_id.to_string(),
+                round_count.to_string(),
+            ));
+            self.stage_run_once(stage, concurrency, deadline, case_skip)
+                .instrument(error_span!("stage", stage=format!("{}-{}", stage_id, round_count)))
+                .await?;
+            if round_count >= round_max
+                || self.stage_state.is_timeout()
+                || self.stage_aborted.is_some()
+            {
+                break;
+            }
+            if self.stage_stop(deadline) {
+                break;
+            }
+            if let Some(ramp_vec) = self.task.flow.stage_ramp(stage_id) {
+                if let (0, true) = ramp_concurrency(&ramp_vec, self.stage_start.elapsed()) {
+                    trace!("stage ramp end");
+                    break;
+                }
+            }
+            round_count += 1;
+            case_skip = 0;
+            case_done = BTreeSet::new();
+        }
+        return Ok(());
+    }
+
+    async fn checkpoint_progress_save(&mut self, round: usize, case: usize) {
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            if let Err(e) = checkpoint
+                .progress_save(round, case, &self.case_seq_done)
+                .await
+            {
+                warn!("checkpoint Err, {}", e);
+            }
+        }
+    }
+
+    /// true if no more case should start, because of cancellation or stage duration
+    fn stage_stop(&mut self, deadline: Instant) -> bool {
+        if self.task.cancel.is_cancelled() {
+            trace!("stage cancelled");
+            return true;
+        }
+        if Instant::now() >= deadline {
+            self.stage_timeout();
+            return true;
+        }
+        false
+    }
+
+    fn stage_timeout(&mut self) {
+        warn!("stage {}", DURATION_EXCEEDED);
+        if let StageState::Ok = self.stage_state {
+            self.stage_state = StageState::Timeout(DURATION_EXCEEDED.to_string());
+        }
+    }
+
+    async fn stage_run_once(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        concurrency: usize,
+        deadline: Instant,
+        case_skip: usize,
+    ) -> Result<(), Error> {
+        trace!("stage run");
+        let start = Utc::now();
+        self.stage_stat = Statistic::new(start);
+
+        let mut loader = self
+            .task
+            .loader
+            .stage(stage.stage())
+            .await
+            .map_err(|e| Loader("stage".to_string(), stage.to_string(), e))?;
+
+        self.case_seq_next = case_skip;
+        self.case_seq_mark = case_skip;
+        self.case_seq_map.clear();
+        if case_skip > 0 {
+            trace!("stage resume, skip {}", case_skip);
+            case_data_skip(stage.as_ref(), loader.as_mut(), case_skip).await?;
+        }
+
+        let mut reporter = self
+            .task
+            .reporter
+            .stage(stage.stage())
+            .await
+            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+
+        reporter
+            .start(Utc::now())
+            .await
+            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+
+        let pre_result = self.stage_pre_run(stage.clone(), reporter.as_mut()).await;
+        let result = match (
+            pre_result,
+            self.task.dry_run,
+            self.task.flow.stage_arrival(stage.stage()),
+        ) {
+            (Err(e), _, _) => Err(e),
+            // no case runs after a failed pre, the stage fails for it
+            (Ok(Some(cause)), _, _) => {
+                warn!("stage pre Fail");
+                self.stage_state = StageState::Fail(cause);
+                Ok(None)
+            }
+            (Ok(None), Some(case_count), _) => self
+                .stage_run_dry(
+                    stage.clone(),
+                    loader.as_mut(),
+                    reporter.as_mut(),
+                    case_count,
+                    deadline,
+                )
+                .await
+                .map(|_| None),
+            (Ok(None), None, Some(arrival)) => self
+                .stage_run_arrival(
+                    stage.clone(),
+                    loader.as_mut(),
+                    reporter.as_mut(),
+                    arrival,
+                    deadline,
+                )
+                .await
+                .map(Some),
+            (Ok(None), None, None) => self
+                .stage_run_io(
+                    stage.clone(),
+                    loader.as_mut(),
+                    reporter.as_mut(),
+                    concurrency,
+                    deadline,
+                )
+                .await
+                .map(|_| None),
+        };
+        // post runs whenever the stage started, whatever became of pre and the cases
+        let post_result = self.stage_post_run(stage.clone(), reporter.as_mut()).await;
+        let result = match (result, post_result) {
+            (Ok(arrival), Ok(_)) => Ok(arrival),
+            (Err(e), _) | (Ok(_), Err(e)) => Err(e),
+        };
+
+        self.stage_stat.end_at(Utc::now());
+        if self.task.cancel.is_cancelled() {
+            self.stage_state = StageState::Cancelled(CANCELLED.to_string());
+        }
+        self.task_stat.merge(&self.stage_stat);
+        let stat = self.stage_stat.clone();
+        let stage_asset = match result {
+            Err(e) => {
+                error!("stage Err, {:?}", e);
+                StageAssetStruct::new(
+                    stage.clone(),
+                    start,
+                    Utc::now(),
+                    StageState::Err(Box::new(e)),
+                    None,
+                    stat,
+                )
+            }
+            Ok(arrival) => match &self.stage_state {
+                StageState::Ok => {
+                    info!("stage Ok");
+                    StageAssetStruct::new(
+                        stage.clone(),
+                        start,
+                        Utc::now(),
+                        StageState::Ok,
+                        arrival,
+                        stat,
+                    )
+                }
+                StageState::Fail(c) => {
+                    warn!("stage Fail");
+                    StageAssetStruct::new(
+                        stage.clone(),
+                        start,
+                        Utc::now(),
+                        StageState::Fail(c.clone()),
+                        arrival,
+                        stat,
+                    )
+                }
+                StageState::Timeout(c) => {
+                    warn!("stage Timeout");
+                    StageAssetStruct::new(
+                        stage.clone(),
+                        start,
+                        Utc::now(),
+                        StageState::Timeout(c.clone()),
+                        arrival,
+                        stat,
+                    )
+                }
+                StageState::Cancelled(c) => {
+                    warn!("stage Cancelled");
+                    StageAssetStruct::new(
+                        stage.clone(),
+                        start,
+                        Utc::now(),
+                        StageState::Cancelled(c.clone()),
+                        arrival,
+                        stat,
+                    )
+                }
+                StageState::Err(_) => unreachable!(),
+            },
+        };
+
+        reporter
+            .end(&stage_asset)
+            .await
+            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+
+        match stage_asset.state() {
+            StageState::Ok => Ok(()),
+            StageState::Fail(_) => Ok(()),
+            StageState::Timeout(_) => Ok(()),
+            StageState::Cancelled(_) => Ok(()),
+            StageState::Err(e) => Err(Unknown(e.to_string())),
+        }
+    }
+
+    /// renders the stage `def` and runs the stage `pre` steps for the round,
+    /// the cases see them with the round number as `stage`.
+    /// gives the cause of the failure of `pre`, if it did not end well
+    async fn stage_pre_run(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        reporter: &mut dyn StageReporter,
+    ) -> Result<Option<String>, Error> {
+        let mut stage_ctx = Map::new();
+        stage_ctx.insert("round".to_string(), json!(self.stage_round_no));
+        self.stage_ctx = Arc::new(stage_ctx.clone());
+        if let Some(def_raw) = self.task.flow.stage_def(self.stage_id) {
+            let mut rc = Map::new();
+            rc.insert("__meta__".to_string(), Value::Object(self.task.flow.meta().clone()));
+            if let Some(def_ctx) = self.task.def_ctx.as_ref() {
+                rc.insert("def".to_string(), Value::Object(def_ctx.as_ref().clone()));
+            }
+            if let Some(pre_ctx) = self.task.pre_ctx.as_ref() {
+                rc.insert("pre".to_string(), Value::Object(pre_ctx.as_ref().clone()));
+            }
+            rc.insert("stage".to_string(), Value::Object(stage_ctx.clone()));
+            let rc = RenderContext::wraps(Value::Object(rc)).unwrap();
+            let def = assign_by_render(self.task.app.get_handlebars(), &rc, def_raw, false)
+                .map_err(|e| Render(format!("stage.{}.def", self.stage_id), e))?;
+            stage_ctx.insert("def".to_string(), Value::Object(def));
+        }
+
+        if let Some(pre_step_vec) = self.pre_step_vec.clone() {
+            let mut pre_arg = CaseArgStruct::new(
+                self.task.flow.clone(),
+                pre_step_vec,
+                Value::Null,
+                stage.clone(),
+                "pre".into(),
+                self.case_option(None),
+            );
+            pre_arg.stage_register(Value::Object(stage_ctx.clone()));
+            let pre_asset = case_run(self.task.app.as_ref(), pre_arg).await;
+            let pre_ctx = match pre_asset.state() {
+                CaseState::Ok(sa_vec) => Ok(pre_ctx_create(sa_vec.as_ref()).await),
+                CaseState::Err(_) | CaseState::Cancelled(_) => Err("pre".to_string()),
+                CaseState::Fail(v) => Err(format!(
+                    "pre step: {}",
+                    v.last().map(|s| s.id().step()).unwrap_or("")
+                )),
+            };
+            reporter
+                .report(&vec![pre_asset])
+                .await
+                .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+            match pre_ctx {
+                Ok(pre_ctx) => {
+                    stage_ctx.insert("pre".to_string(), Value::Object(pre_ctx));
+                }
+                Err(cause) => {
+                    self.stage_ctx = Arc::new(stage_ctx);
+                    return Ok(Some(cause));
+                }
+            }
+        }
+        self.stage_ctx = Arc::new(stage_ctx);
+        Ok(None)
+    }
+
+    /// runs the stage `post` steps for the round, a failure is reported but leaves the stage state as it is
+    async fn stage_post_run(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        reporter: &mut dyn StageReporter,
+    ) -> Result<(), Error> {
+        let post_step_vec = match self.post_step_vec.clone() {
+            Some(post_step_vec) => post_step_vec,
+            None => return Ok(()),
+        };
+        let mut post_arg = CaseArgStruct::new(
+            self.task.flow.clone(),
+            post_step_vec,
+            Value::Null,
+            stage.clone(),
+            "post".into(),
+            self.case_option(None),
+        );
+        post_arg.stage_register(Value::Object(self.stage_ctx.as_ref().clone()));
+        let post_asset = case_run(self.task.app.as_ref(), post_arg).await;
+        match post_asset.state() {
+            CaseState::Ok(_) => trace!("stage post Ok"),
+            CaseState::Fail(_) => warn!("stage post Fail"),
+            CaseState::Err(_) | CaseState::Cancelled(_) => error!("stage post Err"),
+        }
+        reporter
+            .report(&vec![post_asset])
+            .await
+            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))
+    }
+
+    async fn stage_run_io(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        loader: &mut dyn StageLoader,
+        reporter: &mut dyn StageReporter,
+        concurrency: usize,
+        deadline: Instant,
+    ) -> Result<(), Error> {
+        // skipped data of a resumed round counts as loaded
+        let mut load_times = usize::from(self.case_seq_next > 0);
+        let mut exhausted = false;
+        let mut case_data_queue: VecDeque<(String, Value)> = VecDeque::new();
+        let mut case_running = FuturesUnordered::new();
+        let mut case_asset_vec: Vec<Box<dyn CaseAsset>> = Vec::with_capacity(concurrency);
+        let ramp_vec = self.task.flow.stage_ramp(stage.stage());
+        let mut level_reported = None;
+        loop {
+            let (level, ramp_end) = match ramp_vec.as_ref() {
+                Some(ramp_vec) => ramp_concurrency(ramp_vec, self.stage_start.elapsed()),
+                None => (concurrency, true),
+            };
+            if ramp_vec.is_some() && level_reported != Some(level) {
+                trace!("stage concurrency {}", level);
+                reporter
+                    .concurrency(Utc::now(), level)
+                    .await
+                    .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+                level_reported = Some(level);
+            }
+            if !exhausted && ramp_end && level == 0 {
+                trace!("stage ramp end");
+                exhausted = true;
+            }
+            if !exhausted && self.stage_stop(deadline) {
+                exhausted = true;
+            }
+
+            while !exhausted && case_running.len() < level {
+                if self.stage_stop(deadline) {
+                    exhausted = true;
+                    break;
+                }
+
+                let case_data = self.case_data_undone(
+                    stage.as_ref(),
+                    loader,
+                    &mut case_data_queue,
+                    &mut load_times,
+                    concurrency,
+                )
+                .await?;
+                let (id, data) = match case_data {
+                    Some(case_data) => case_data,
+                    None => {
+                        exhausted = true;
+                        break;
+                    }
+                };
+                let case_arg = self.case_arg_create(stage.clone(), id, data);
+                case_running.push(case_run_arc(
+                    self.task.app.clone(),
+                    case_arg,
+                    deadline,
+                    self.task.cancel.clone(),
+                ));
+            }
+
+            let case_asset = if ramp_end {
+                case_running.next().await
+            } else {
+                match timeout(RAMP_TICK, case_running.next()).await {
+                    Ok(case_asset) => case_asset,
+                    Err(_) => continue,
+                }
+            };
+            match case_asset {
+                Some(case_asset) => {
+                    if self.case_abort_check(case_asset.as_ref()) {
+                        exhausted = true;
+                    }
+                    case_asset_vec.push(case_asset);
+                    if case_asset_vec.len() >= concurrency {
+                        self.case_asset_vec_report(
+                            stage.as_ref(),
+                            reporter,
+                            case_asset_vec.split_off(0),
+                        )
+                        .await?;
+                    }
+                }
+                None if exhausted => break,
+                None => sleep(RAMP_TICK).await,
+            }
+        }
+
+        if !case_asset_vec.is_empty() {
+            self.case_asset_vec_report(stage.as_ref(), reporter, case_asset_vec)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn stage_run_arrival(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        loader: &mut dyn StageLoader,
+        reporter: &mut dyn StageReporter,
+        arrival: Arrival,
+        deadline: Instant,
+    ) -> Result<ArrivalAssetStruct, Error> {
+        let interval = Duration::from_secs_f64(1.0 / arrival.rate() as f64);
+        let max_in_flight = arrival.max_in_flight();
+        let mut arrival_asset = ArrivalAssetStruct::new(arrival.rate());
+        // skipped data of a resumed round counts as loaded
+        let mut load_times = usize::from(self.case_seq_next > 0);
+        let mut exhausted = false;
+        let mut case_data_queue: VecDeque<(String, Value)> = VecDeque::new();
+        let mut case_running = FuturesUnordered::new();
+        let mut case_asset_vec: Vec<Box<dyn CaseAsset>> = Vec::with_capacity(max_in_flight);
+        let mut next_start = Instant::now();
+        loop {
+            if !exhausted && self.stage_stop(deadline) {
+                exhausted = true;
+            }
+
+            while !exhausted && Instant::now() >= next_start {
+                let scheduled_at = next_start;
+                next_start += interval;
+                if case_running.len() >= max_in_flight {
+                    arrival_asset.scheduled += 1;
+                    arrival_asset.skipped += 1;
+                    continue;
+                }
+
+                let case_data = self.case_data_undone(
+                    stage.as_ref(),
+                    loader,
+                    &mut case_data_queue,
+                    &mut load_times,
+                    max_in_flight,
+                )
+                .await?;
+                let (id, data) = match case_data {
+                    Some(case_data) => case_data,
+                    None => {
+                        exhausted = true;
+                        break;
+                    }
+                };
+                arrival_asset.scheduled += 1;
+                arrival_asset.started += 1;
+                if scheduled_at.elapsed() > interval {
+                    arrival_asset.late += 1;
+                }
+                let case_arg = self.case_arg_create(stage.clone(), id, data);
+                case_running.push(case_run_arc(
+                    self.task.app.clone(),
+                    case_arg,
+                    deadline,
+                    self.task.cancel.clone(),
+                ));
+            }
+
+            let case_asset = if exhausted {
+                case_running.next().await
+            } else {
+                let wait = next_start.min(deadline).saturating_duration_since(Instant::now());
+                match timeout(wait, case_running.next()).await {
+                    Ok(None) => {
+                        sleep(wait).await;
+                        continue;
+                    }
+                    Ok(case_asset) => case_asset,
+                    Err(_) => continue,
+                }
+            };
+            match case_asset {
+                Some(case_asset) => {
+                    if self.case_abort_check(case_asset.as_ref()) {
+                        exhausted = true;
+                    }
+                    case_asset_vec.push(case_asset);
+                    if case_asset_vec.len() >= max_in_flight {
+                        self.case_asset_vec_report(
+                            stage.as_ref(),
+                            reporter,
+                            case_asset_vec.split_off(0),
+                        )
+                        .await?;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        if !case_asset_vec.is_empty() {
+            self.case_asset_vec_report(stage.as_ref(), reporter, case_asset_vec)
+                .await?;
+        }
+        info!(
+            "stage arrival scheduled {}, started {}, late {}, skipped {}",
+            arrival_asset.scheduled,
+            arrival_asset.started,
+            arrival_asset.late,
+            arrival_asset.skipped
+        );
+        Ok(arrival_asset)
+    }
+
+    /// runs the first `case_count` cases one by one
+    async fn stage_run_dry(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        loader: &mut dyn StageLoader,
+        reporter: &mut dyn StageReporter,
+        case_count: usize,
+        deadline: Instant,
+    ) -> Result<(), Error> {
+        let mut load_times = 0;
+        let mut case_data_queue: VecDeque<(String, Value)> = VecDeque::new();
+        let mut case_asset_vec: Vec<Box<dyn CaseAsset>> = Vec::with_capacity(case_count);
+        while case_asset_vec.len() < case_count && !self.stage_stop(deadline) {
+            let case_data = case_data_next(
+                stage.as_ref(),
+                loader,
+                &mut case_data_queue,
+                &mut load_times,
+                case_count,
+            )
+            .await?;
+            let (id, data) = match case_data {
+                Some(case_data) => case_data,
+                None => break,
+            };
+            let case_arg = self.case_arg_create(stage.clone(), id, data);
+            let case_asset =
+                case_run_arc(self.task.app.clone(), case_arg, deadline, self.task.cancel.clone()).await;
+            case_asset_vec.push(case_asset);
+        }
+
+        self.case_asset_vec_report(stage.as_ref(), reporter, case_asset_vec)
+            .await
+    }
+
+    /// true once the stage abort policy is hit, no more case should start
+    fn case_abort_check(&mut self, case_asset: &dyn CaseAsset) -> bool {
+        if self.stage_aborted.is_some() {
+            return true;
+        }
+        let abort = match self.stage_abort.as_ref() {
+            Some(abort) => abort,
+            None => return false,
+        };
+        if case_asset.state().is_cancelled() {
+            return false;
+        }
+
+        let fail = !case_asset.state().is_ok();
+        self.case_fail_consecutive = if fail { self.case_fail_consecutive + 1 } else { 0 };
+        self.case_fail_window.push_back(fail);
+        if self.case_fail_window.len() > abort.window() {
+            self.case_fail_window.pop_front();
+        }
+
+        let cause = if abort
+            .consecutive()
+            .filter(|c| self.case_fail_consecutive >= *c)
+            .is_some()
+        {
+            Some(format!("{} consecutive case fail", self.case_fail_consecutive))
+        } else if let Some(error_ratio) = abort.error_ratio() {
+            let fail_count = self.case_fail_window.iter().filter(|f| **f).count();
+            let ratio = fail_count as f64 / abort.window() as f64;
+            if self.case_fail_window.len() >= abort.window() && ratio > error_ratio {
+                Some(format!(
+                    "error ratio {} over last {} case",
+                    ratio,
+                    abort.window()
+                ))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        match cause {
+            Some(cause) => {
+                warn!("stage abort, {}", cause);
+                let cause = format!("abort: {}", cause);
+                self.stage_aborted = Some(abort.scope());
+                self.stage_state = StageState::Fail(cause);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn case_asset_vec_report(
+        &mut self,
+        stage: &StageIdStruct,
+        reporter: &mut dyn StageReporter,
+        case_asset_vec: Vec<Box<dyn CaseAsset>>,
+    ) -> Result<(), Error> {
+        for ca in case_asset_vec.iter() {
+            self.stage_stat.record(ca.as_ref());
+        }
+        let first_fail = case_asset_vec
+            .iter()
+            .find(|ca| !ca.state().is_ok() && !ca.state().is_cancelled());
+        if let (Some(cause_case), None) = (first_fail, &self.stage_aborted) {
+            let cause = match cause_case.state() {
+                CaseState::Err(_) => format!("case: {}", cause_case.id()),
+                CaseState::Fail(v) => {
+                    let last_step_id = v
+                        .last()
+                        .map(|s| s.id().to_string())
+                        .or_else(|| Some(String::new()))
+                        .unwrap();
+                    format!("step: {}", last_step_id)
+                }
+                CaseState::Ok(_) | CaseState::Cancelled(_) => String::new(),
+            };
+            self.stage_state = StageState::Fail(cause);
+        }
+        reporter
+            .report(&case_asset_vec)
+            .await
+            .map_err(|e| Reporter("stage".to_string(), stage.to_string(), e))?;
+
+        // cancelled cases hold the mark, they run again on resume
+        for ca in case_asset_vec.iter() {
+            if let Some(seq) = self.case_seq_map.remove(ca.id().case()) {
+                if !ca.state().is_cancelled() {
+                    self.case_seq_done.insert(seq);
+                }
+            }
+        }
+        while self.case_seq_done.remove(&self.case_seq_mark) {
+            self.case_seq_mark += 1;
+        }
+        self.checkpoint_progress_save(self.stage_round_no, self.case_seq_mark)
+            .await;
+
+        // cases cancelled by a signal leave the stage to be cancelled, not timed out
+        if case_asset_vec
+            .iter()
+            .any(|ca| matches!(ca.state(), CaseState::Cancelled(c) if c == DURATION_EXCEEDED))
+        {
+            self.stage_timeout();
+        }
+        Ok(())
+    }
+
+    /// next case data, passing over the cases a resumed round already reported
+    async fn case_data_undone(
+        &mut self,
+        stage: &StageIdStruct,
+        loader: &mut dyn StageLoader,
+        case_data_queue: &mut VecDeque<(String, Value)>,
+        load_times: &mut usize,
+        size: usize,
+    ) -> Result<Option<(String, Value)>, Error> {
+        loop {
+            let case_data = case_data_next(stage, loader, case_data_queue, load_times, size).await?;
+            if case_data.is_some() && self.case_seq_done.contains(&self.case_seq_next) {
+                trace!("stage resume, skip case {}", self.case_seq_next);
+                self.case_seq_next += 1;
+                continue;
+            }
+            return Ok(case_data);
+        }
+    }
+
+    fn case_option(&self, timeout: Option<Duration>) -> CaseOption {
+        CaseOption {
+            pre_ctx: self.task.pre_ctx.clone(),
+            def_ctx: self.task.def_ctx.clone(),
+            timeout,
+        }
+    }
+
+    fn case_arg_create(
+        &mut self,
+        stage: Arc<StageIdStruct>,
+        id: String,
+        data: Value,
+    ) -> CaseArgStruct {
+        self.case_seq_map.insert(id.clone(), self.case_seq_next);
+        self.case_seq_next += 1;
+        let mut case_arg = CaseArgStruct::new(
+            self.task.flow.clone(),
+            self.step_vec.clone(),
+            data,
+            stage.clone(),
+            id,
+            self.case_option(self.task.flow.stage_case_timeout(stage.stage())),
+        );
+        case_arg.stage_register(Value::Object(self.stage_ctx.as_ref().clone()));
+        case_arg
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn case_window_refill() {
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": {
+            "concurrency": 2,
+            "step": { "a": { "action": { "x": { "record": {
+                "n": "{{case.n}}",
+                "sleep": "{{#if (eq case.n 0)}}400{{else}}50{{/if}}"
+            } } } } }
+        } }
+    });
+    let (state, value_vec, _) = super::task_record_run(flow, 6).await;
+    assert_eq!(state, "O");
+
+    // `n` of the case starting or ending at each record
+    let event_vec: Vec<(bool, String)> = value_vec
+        .iter()
+        .map(|v| match v.get("end") {
+            Some(end) => (false, end["n"].as_str().unwrap().to_string()),
+            None => (true, v["n"].as_str().unwrap().to_string()),
+        })
+        .collect();
+    assert_eq!(event_vec.len(), 12);
+
+    let mut running = 0;
+    let mut running_max = 0;
+    for (start, _) in event_vec.iter() {
+        running = if *start { running + 1 } else { running - 1 };
+        running_max = running_max.max(running);
+    }
+    assert_eq!(running_max, 2);
+
+    // the slow case holds one slot while the other slot goes through every other case
+    let slow_end = event_vec
+        .iter()
+        .position(|e| e == &(false, "0".to_string()))
+        .unwrap();
+    let last_start = event_vec
+        .iter()
+        .rposition(|(start, _)| *start)
+        .unwrap();
+    assert_eq!(event_vec[last_start].1, "5");
+    assert!(last_start < slow_end);
+}
@@ -0,0 +1,644 @@
+use std::collections::{HashMap, HashSet};
+
+use handlebars::template::{HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::Path;
+
+use chord_core::flow::{Flow, GOTO_END};
+use chord_core::value::{Map, Value};
+
+use crate::model::helper;
+
+/// helpers whose block is rendered in the same context as the helper itself,
+/// the block of any other helper, like `each` or `with`, is rendered in a context of its own
+static SCOPE_KEEP_HELPERS: &[&str] = &["if", "unless"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0} template invalid:\n{1}")]
+    Invalid(String, String),
+
+    #[error("{0} unknown helper `{1}`")]
+    HelperUnknown(String, String),
+
+    #[error("{0} undefined reference `{1}`")]
+    RefUndefined(String, String),
+}
+
+impl Error {
+    /// path of the flow entry holding the template, in the form of `a.b.c`
+    pub fn path(&self) -> &str {
+        match self {
+            Error::Invalid(p, _) | Error::HelperUnknown(p, _) | Error::RefUndefined(p, _) => {
+                p.as_str()
+            }
+        }
+    }
+}
+
+/// checks the references and helpers of every template in the flow against what is defined
+/// before the template renders, `case_field` holds the case fields of stages known before loading
+pub fn check(flow: &Flow, case_field: &HashMap<String, Vec<String>>) -> Vec<Error> {
+    let checker = Checker {
+        helper_vec: helper::name_vec(),
+        meta_key_vec: flow.meta().keys().map(|k| k.as_str()).collect(),
+        def_key_vec: flow.def().map(|d| d.keys().map(|k| k.as_str()).collect()),
+    };
+    let mut err_vec = vec![];
+
+    let empty = Scope {
+        step: HashMap::new(),
+        pre: None,
+        def: false,
+        case: None,
+        task: false,
+        stage: None,
+        root: HashSet::new(),
+    };
+    if let Some(def) = flow.def() {
+        checker.def_check("def", def, &empty, &mut err_vec);
+    }
+
+    let pre_step_id_vec = flow.pre_step_id_vec().unwrap_or_default();
+    let pre = if pre_step_id_vec.is_empty() {
+        None
+    } else {
+        let pre_scope = Scope {
+            def: checker.def_key_vec.is_some(),
+            ..empty.clone()
+        };
+        checker.group_check(flow, "pre", &pre_step_id_vec, &pre_scope, &mut err_vec);
+        Some(step_action_map(
+            flow,
+            &pre_step_id_vec,
+            pre_step_id_vec.len(),
+        ))
+    };
+    let task_scope = Scope {
+        pre,
+        def: checker.def_key_vec.is_some(),
+        ..empty
+    };
+
+    for stage_id in flow.stage_id_vec() {
+        let path = format!("stage.{}", stage_id);
+        let mut stage_scope = Scope {
+            stage: Some(StageScope {
+                def_key_vec: None,
+                pre: None,
+            }),
+            ..task_scope.clone()
+        };
+        if let Some(def) = flow.stage_def(stage_id) {
+            checker.def_check(format!("{}.def", path).as_str(), def, &stage_scope, &mut err_vec);
+            stage_scope.stage = Some(StageScope {
+                def_key_vec: Some(def.keys().map(|k| k.as_str()).collect()),
+                pre: None,
+            });
+        }
+        if let Some(pre_step_id_vec) = flow.stage_pre_step_id_vec(stage_id) {
+            let pre_path = format!("{}.pre", path);
+            checker.group_check(flow, &pre_path, &pre_step_id_vec, &stage_scope, &mut err_vec);
+            if let Some(stage) = stage_scope.stage.as_mut() {
+                stage.pre = Some(step_action_map(
+                    flow,
+                    &pre_step_id_vec,
+                    pre_step_id_vec.len(),
+                ));
+            }
+        }
+
+        let step_id_vec = flow.stage_step_id_vec(stage_id);
+        let case_scope = Scope {
+            case: Some(case_field.get(stage_id).map(|f| f.as_slice())),
+            ..stage_scope.clone()
+        };
+        checker.group_check(flow, &path, &step_id_vec, &case_scope, &mut err_vec);
+
+        if let Some(post_step_id_vec) = flow.stage_post_step_id_vec(stage_id) {
+            let post_path = format!("{}.post", path);
+            checker.group_check(flow, &post_path, &post_step_id_vec, &stage_scope, &mut err_vec);
+        }
+    }
+
+    if let Some(post_step_id_vec) = flow.post_step_id_vec() {
+        let post_scope = Scope {
+            task: true,
+            ..task_scope
+        };
+        checker.group_check(flow, "post", &post_step_id_vec, &post_scope, &mut err_vec);
+    }
+    err_vec
+}
+
+/// names visible to a template
+#[derive(Clone)]
+struct Scope<'f> {
+    /// steps under `step` with their action ids
+    step: HashMap<&'f str, Vec<&'f str>>,
+    /// steps under `pre.step` with their action ids
+    pre: Option<HashMap<&'f str, Vec<&'f str>>>,
+    def: bool,
+    /// `None` if there is no case, `Some(None)` if its fields are unknown
+    case: Option<Option<&'f [String]>>,
+    /// `task.state` and `task.cause` of the ended task, seen by `post` steps
+    task: bool,
+    /// `None` outside of a stage
+    stage: Option<StageScope<'f>>,
+    /// action ids, registered names and names bound by the action itself
+    root: HashSet<String>,
+}
+
+/// names under `stage`, the round is always there
+#[derive(Clone)]
+struct StageScope<'f> {
+    def_key_vec: Option<Vec<&'f str>>,
+    /// steps under `stage.pre.step` with their action ids
+    pre: Option<HashMap<&'f str, Vec<&'f str>>>,
+}
+
+struct Checker<'f> {
+    helper_vec: Vec<&'static str>,
+    meta_key_vec: Vec<&'f str>,
+    def_key_vec: Option<Vec<&'f str>>,
+}
+
+impl<'f> Checker<'f> {
+    fn group_check(
+        &self,
+        flow: &'f Flow,
+        path: &str,
+        step_id_vec: &[&'f str],
+        base: &Scope<'f>,
+        err_vec: &mut Vec<Error>,
+    ) {
+        // with a backward goto any step of the group may have run before
+        let loop_back = step_id_vec.iter().enumerate().any(|(idx, sid)| {
+            flow.step_then(sid).unwrap_or_default().iter().any(|then| {
+                then.goto()
+                    .filter(|g| *g != GOTO_END)
+                    .and_then(|g| step_id_vec.iter().position(|s| s == &g))
+                    .map(|g| g <= idx)
+                    .unwrap_or(false)
+            })
+        });
+        let reg_key_set: HashSet<String> = step_id_vec
+            .iter()
+            .flat_map(|sid| flow.step_then(sid).unwrap_or_default())
+            .flat_map(|then| {
+                then.reg()
+                    .map(|r| r.keys().cloned().collect::<Vec<String>>())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        for (idx, sid) in step_id_vec.iter().enumerate() {
+            let visible = if loop_back { step_id_vec.len() } else { idx };
+            let mut scope = Scope {
+                step: step_action_map(flow, step_id_vec, visible),
+                root: reg_key_set.clone(),
+                ..base.clone()
+            };
+            let step_path = format!("{}.step.{}", path, sid);
+            if let Some(cond) = flow.step_if(sid) {
+                self.template_check(
+                    format!("{}.if", step_path).as_str(),
+                    format!("{{{{{}}}}}", cond).as_str(),
+                    &scope,
+                    err_vec,
+                );
+            }
+            let aid_vec = flow.step_action_id_vec(sid);
+            for aid in aid_vec.iter() {
+                let args = flow.step_action_args(sid, aid);
+                let mut action_scope = scope.clone();
+                action_key_collect(flow.step_action_func(sid, aid), args, &mut action_scope.root);
+                let action_path =
+                    format!("{}.action.{}.{}", step_path, aid, flow.step_action_func(sid, aid));
+                self.value_check(action_path.as_str(), args, &action_scope, err_vec);
+                scope.root.insert(aid.to_string());
+            }
+
+            if let Some(retry) = flow.step_retry(sid) {
+                if let Some(cond) = retry.cond() {
+                    let mut retry_scope = scope.clone();
+                    retry_scope.root.insert("retry".to_string());
+                    self.template_check(
+                        format!("{}.retry.cond", step_path).as_str(),
+                        format!("{{{{{}}}}}", cond).as_str(),
+                        &retry_scope,
+                        err_vec,
+                    );
+                }
+            }
+
+            for then in flow.step_then(sid).unwrap_or_default() {
+                let then_path = format!("{}.then", step_path);
+                if let Some(cond) = then.cond() {
+                    self.template_check(
+                        format!("{}.cond", then_path).as_str(),
+                        format!("{{{{{}}}}}", cond).as_str(),
+                        &scope,
+                        err_vec,
+                    );
+                }
+                if let Some(reg) = then.reg() {
+                    for (k, v) in reg {
+                        let reg_path = format!("{}.reg.{}", then_path, k);
+                        self.value_check(reg_path.as_str(), v, &scope, err_vec);
+                    }
+                }
+            }
+
+            // a defer runs at the end of the case, any step may have run by then
+            let mut defer_scope = Scope {
+                step: step_action_map(flow, step_id_vec, step_id_vec.len()),
+                root: reg_key_set.clone(),
+                ..base.clone()
+            };
+            for did in flow.step_defer_id_vec(sid) {
+                let args = flow.step_defer_args(sid, did);
+                let mut action_scope = defer_scope.clone();
+                action_key_collect(flow.step_defer_func(sid, did), args, &mut action_scope.root);
+                let defer_path =
+                    format!("{}.defer.{}.{}", step_path, did, flow.step_defer_func(sid, did));
+                self.value_check(defer_path.as_str(), args, &action_scope, err_vec);
+                defer_scope.root.insert(did.to_string());
+            }
+        }
+    }
+
+    /// entries of a `def`, each sees the ones before it
+    fn def_check(&self, path: &str, def: &Map, base: &Scope, err_vec: &mut Vec<Error>) {
+        let mut scope = base.clone();
+        for (k, v) in def {
+            self.value_check(format!("{}.{}", path, k).as_str(), v, &scope, err_vec);
+            scope.root.insert(k.to_string());
+        }
+    }
+
+    fn value_check(&self, path: &str, value: &Value, scope: &Scope, err_vec: &mut Vec<Error>) {
+        match value {
+            Value::String(text) => self.template_check(path, text, scope, err_vec),
+            Value::Object(map) => {
+                for (k, v) in map {
+                    self.value_check(format!("{}.{}", path, k).as_str(), v, scope, err_vec);
+                }
+            }
+            Value::Array(vec) => {
+                for v in vec {
+                    self.value_check(path, v, scope, err_vec);
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+
+    fn template_check(&self, path: &str, text: &str, scope: &Scope, err_vec: &mut Vec<Error>) {
+        if !text.contains("{{") {
+            return;
+        }
+        match Template::compile(text) {
+            Ok(template) => self.element_check(path, &template.elements, scope, 0, err_vec),
+            Err(e) => err_vec.push(Error::Invalid(path.to_string(), e.to_string())),
+        }
+    }
+
+    /// `depth` counts the blocks around the elements which changed the context,
+    /// within them only references reaching back to the context of the template are checked
+    fn element_check(
+        &self,
+        path: &str,
+        element_vec: &[TemplateElement],
+        scope: &Scope,
+        depth: usize,
+        err_vec: &mut Vec<Error>,
+    ) {
+        for element in element_vec {
+            match element {
+                TemplateElement::Expression(ht) | TemplateElement::HtmlExpression(ht) => {
+                    self.helper_template_check(path, ht, scope, depth, err_vec);
+                }
+                TemplateElement::HelperBlock(ht) => {
+                    self.helper_template_check(path, ht, scope, depth, err_vec);
+                    let scope_keep = matches!(&ht.name, Parameter::Name(n) if SCOPE_KEEP_HELPERS.contains(&n.as_str()));
+                    let block_depth = if scope_keep { depth } else { depth + 1 };
+                    for block in [ht.template.as_ref(), ht.inverse.as_ref()]
+                        .into_iter()
+                        .flatten()
+                    {
+                        self.element_check(path, &block.elements, scope, block_depth, err_vec);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn helper_template_check(
+        &self,
+        path: &str,
+        ht: &HelperTemplate,
+        scope: &Scope,
+        depth: usize,
+        err_vec: &mut Vec<Error>,
+    ) {
+        let name_only = !ht.block && ht.params.is_empty() && ht.hash.is_empty();
+        match &ht.name {
+            Parameter::Name(name) if name_only => {
+                if !self.helper_vec.contains(&name.as_str()) {
+                    self.ref_check(path, name, scope, depth, err_vec);
+                }
+            }
+            Parameter::Name(name) => {
+                if !self.helper_vec.contains(&name.as_str()) {
+                    err_vec.push(Error::HelperUnknown(path.to_string(), name.to_string()));
+                }
+            }
+            other => self.param_check(path, other, scope, depth, err_vec),
+        }
+        for param in ht.params.iter().chain(ht.hash.values()) {
+            self.param_check(path, param, scope, depth, err_vec);
+        }
+    }
+
+    fn param_check(
+        &self,
+        path: &str,
+        param: &Parameter,
+        scope: &Scope,
+        depth: usize,
+        err_vec: &mut Vec<Error>,
+    ) {
+        match param {
+            Parameter::Name(name) => self.ref_check(path, name, scope, depth, err_vec),
+            Parameter::Path(Path::Relative((_, raw))) => {
+                self.ref_check(path, raw, scope, depth, err_vec)
+            }
+            Parameter::Path(Path::Local(_)) | Parameter::Literal(_) => {}
+            Parameter::Subexpression(se) => {
+                if let TemplateElement::Expression(ht) = se.as_element() {
+                    self.helper_template_check(path, ht, scope, depth, err_vec);
+                }
+            }
+        }
+    }
+
+    fn ref_check(
+        &self,
+        path: &str,
+        raw: &str,
+        scope: &Scope,
+        depth: usize,
+        err_vec: &mut Vec<Error>,
+    ) {
+        let (name, depth) = match raw
+            .strip_prefix("@root.")
+            .or_else(|| raw.strip_prefix("@root/"))
+        {
+            Some(name) => (name, 0),
+            None => {
+                let mut name = raw;
+                let mut depth = depth;
+                while let Some(up) = name.strip_prefix("../") {
+                    name = up;
+                    depth = depth.saturating_sub(1);
+                }
+                (name, depth)
+            }
+        };
+        if depth > 0 || name.starts_with('@') || name.starts_with('.') || name.starts_with("this") {
+            return;
+        }
+        let seg_vec = path_seg_vec(name);
+        let seg = |idx: usize| seg_vec.get(idx).map(|s| s.as_str());
+        let defined = match seg(0).unwrap_or("") {
+            "step" => step_ref_defined(&scope.step, seg(1), seg(2)),
+            "pre" => match (scope.pre.as_ref(), seg(1)) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(pre), Some("step")) => step_ref_defined(pre, seg(2), seg(3)),
+                (Some(_), Some(_)) => false,
+            },
+            "def" => match (scope.def, seg(1)) {
+                (false, _) => false,
+                (true, None) => true,
+                (true, Some(k)) => self
+                    .def_key_vec
+                    .as_ref()
+                    .map(|d| d.contains(&k))
+                    .unwrap_or(false),
+            },
+            "case" => match (scope.case, seg(1)) {
+                (_, None) => true,
+                (None, Some(_)) => false,
+                (Some(None), Some(_)) => true,
+                (Some(Some(field_vec)), Some(k)) => field_vec.iter().any(|f| f == k),
+            },
+            "task" if scope.task => matches!(seg(1), None | Some("state") | Some("cause")),
+            "stage" if scope.stage.is_some() => {
+                let stage = scope.stage.as_ref().unwrap();
+                match seg(1) {
+                    None => true,
+                    Some("round") => seg(2).is_none(),
+                    Some("def") => match (stage.def_key_vec.as_ref(), seg(2)) {
+                        (None, _) => false,
+                        (Some(_), None) => true,
+                        (Some(d), Some(k)) => d.contains(&k),
+                    },
+                    Some("pre") => match (stage.pre.as_ref(), seg(2)) {
+                        (None, _) => false,
+                        (Some(_), None) => true,
+                        (Some(pre), Some("step")) => step_ref_defined(pre, seg(3), seg(4)),
+                        (Some(_), Some(_)) => false,
+                    },
+                    Some(_) => false,
+                }
+            }
+            "__meta__" => seg(1)
+                .map(|k| self.meta_key_vec.contains(&k))
+                .unwrap_or(true),
+            root => scope.root.contains(root),
+        };
+        if !defined {
+            err_vec.push(Error::RefUndefined(path.to_string(), raw.to_string()));
+        }
+    }
+}
+
+fn step_ref_defined(
+    step: &HashMap<&str, Vec<&str>>,
+    step_id: Option<&str>,
+    action_id: Option<&str>,
+) -> bool {
+    match (step_id, action_id) {
+        (None, _) => true,
+        (Some(sid), None) => step.contains_key(sid),
+        (Some(sid), Some(aid)) => step.get(sid).map(|a| a.contains(&aid)).unwrap_or(false),
+    }
+}
+
+/// the first `count` steps with their action ids
+fn step_action_map<'f>(
+    flow: &'f Flow,
+    step_id_vec: &[&'f str],
+    count: usize,
+) -> HashMap<&'f str, Vec<&'f str>> {
+    step_id_vec
+        .iter()
+        .take(count)
+        .map(|sid| (*sid, flow.step_action_id_vec(sid)))
+        .collect()
+}
+
+/// names the `iter_*` actions bind for each element
+static ITER_KEYS: &[&str] = &["item", "index", "value"];
+
+/// names an action of `func` binds for its own templates, with the ids of the actions nested in
+/// its args, other keys of the args are no names a template can refer to
+fn action_key_collect(func: &str, args: &Value, key_set: &mut HashSet<String>) {
+    if func.starts_with("iter_") {
+        key_set.extend(ITER_KEYS.iter().map(|k| k.to_string()));
+    } else if func == "try" {
+        key_set.insert("error".to_string());
+    }
+
+    let action_map_vec: Vec<&Value> = match func {
+        "block" => vec![args],
+        "try" => vec![&args["block"], &args["catch"], &args["finally"]],
+        "parallel" => vec![&args["action"]],
+        "match" | "while" => args
+            .as_object()
+            .map(|cond_map| cond_map.values().collect())
+            .unwrap_or_default(),
+        f if f.starts_with("iter_") => vec![&args["block"]],
+        _ => vec![],
+    };
+    for action_map in action_map_vec.into_iter().filter_map(|m| m.as_object()) {
+        for (aid, action) in action_map {
+            key_set.insert(aid.to_string());
+            if let Some((func, args)) = action.as_object().and_then(|a| a.iter().next_back()) {
+                action_key_collect(func, args, key_set);
+            }
+        }
+    }
+}
+
+/// segments of a template path like `a.b/c.[d e]`
+fn path_seg_vec(raw: &str) -> Vec<String> {
+    let mut seg_vec = vec![];
+    let mut seg = String::new();
+    let mut bracket = false;
+    for c in raw.chars() {
+        match c {
+            '[' if !bracket => bracket = true,
+            ']' if bracket => bracket = false,
+            '.' | '/' if !bracket => seg_vec.push(std::mem::take(&mut seg)),
+            c => seg.push(c),
+        }
+    }
+    seg_vec.push(seg);
+    seg_vec
+}
+
+
+#[cfg(test)]
+fn check_ref_vec(flow: Value, case_field: &[&str]) -> Vec<String> {
+    let flow = Flow::new(flow, std::path::Path::new(".")).unwrap();
+    let mut case_field_map = HashMap::new();
+    case_field_map.insert(
+        "s".to_string(),
+        case_field.iter().map(|f| f.to_string()).collect(),
+    );
+    check(&flow, &case_field_map)
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+#[test]
+fn action_scope() {
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": {
+            "a": { "action": {
+                "h": { "let": { "url": "u", "body": "{{url}}" } },
+                "l": { "log": "{{item}} {{error}}" },
+                "m": { "iter_map": {
+                    "iter": "{{case.list}}",
+                    "block": { "v": { "let": "{{item}}-{{index}}" } }
+                } },
+                "n": { "iter_filter": {
+                    "iter": "{{m}}",
+                    "cond": "eq value.w item",
+                    "block": { "w": { "let": "{{item}} {{v}}" } }
+                } },
+                "t": { "try": {
+                    "block": { "x": { "while": { "(lt 1 2)": { "z": { "let": "{{case.lost}}" } } } } },
+                    "catch": { "y": { "log": "{{error.code}} {{x}} {{z}}" } }
+                } }
+            } }
+        } } }
+    });
+    assert_eq!(
+        check_ref_vec(flow, &["list"]),
+        vec![
+            "stage.s.step.a.action.h.let.body undefined reference `url`",
+            "stage.s.step.a.action.l.log undefined reference `item`",
+            "stage.s.step.a.action.l.log undefined reference `error`",
+            "stage.s.step.a.action.n.iter_filter.block.w.let undefined reference `v`",
+            "stage.s.step.a.action.t.try.block.x.while.(lt 1 2).z.let undefined reference `case.lost`",
+        ]
+    );
+}
+
+#[test]
+fn step_scope() {
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": {
+            "a": {
+                "action": { "x": { "log": "{{n}} {{m}} {{step.b.y}}" } },
+                "then": [{ "reg": { "n": 1 } }],
+                "defer": { "d": { "log": "{{step.b.y}} {{step.b.z}} {{x}}" } }
+            },
+            "b": {
+                "action": { "y": { "log": "{{n}} {{step.a.x}}" } }
+            }
+        } } }
+    });
+    assert_eq!(
+        check_ref_vec(flow, &[]),
+        vec![
+            "stage.s.step.a.action.x.log undefined reference `m`",
+            "stage.s.step.a.action.x.log undefined reference `step.b.y`",
+            "stage.s.step.a.defer.d.log undefined reference `step.b.z`",
+            "stage.s.step.a.defer.d.log undefined reference `x`",
+        ]
+    );
+}
+
+#[test]
+fn block_scope() {
+    use chord_core::value::json;
+
+    let flow = json!({
+        "version": "0.0.2",
+        "stage": { "s": { "step": {
+            "a": { "action": { "x": { "log":
+                "{{#if case.name}}{{lost}}{{/if}}{{#each case.list}}{{name}}{{../case.name}}{{@root.gone}}{{../../far}}{{/each}}"
+            } } }
+        } } }
+    });
+    assert_eq!(
+        check_ref_vec(flow, &["name", "list"]),
+        vec![
+            "stage.s.step.a.action.x.log undefined reference `lost`",
+            "stage.s.step.a.action.x.log undefined reference `@root.gone`",
+            "stage.s.step.a.action.x.log undefined reference `../../far`",
+        ]
+    );
+}
@@ -1,7 +1,13 @@
 pub use flow::app_create;
+pub use flow::CancelToken;
+pub use flow::CancelTrigger;
+pub use flow::Checkpoint;
+pub use flow::JobCheckpoint;
 pub use flow::CTX_ID;
 pub use flow::TaskIdStruct;
 pub use flow::TaskRunner;
+pub use flow::TemplateError;
+pub use flow::template_check;
 pub use model::app::App;
 
 mod flow;
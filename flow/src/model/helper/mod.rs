@@ -10,61 +10,75 @@ mod num;
 mod obj;
 mod str;
 
-pub fn register(handlebars: &mut Handlebars) {
-    //handlebars-3.5.4/src/registry.rs:118
-    //literal
-    handlebars.register_helper(
-        "bl",
-        Box::new(LiteralHelper {
-            literal: "{".into(),
-        }),
-    );
-    handlebars.register_helper(
-        "br",
-        Box::new(LiteralHelper {
-            literal: "}".into(),
-        }),
-    );
-
-    //json
-    handlebars.register_helper("json", Box::new(json::JSON));
-    handlebars.register_helper("json_path", Box::new(json::PATH));
-
-    //object
-    handlebars.register_helper("obj", Box::new(obj::OBJ));
-    handlebars.register_helper("obj_contains_key", Box::new(obj::OBJ_CONTAINS_KEY));
-
-    // bool
-    handlebars.register_helper("bool", Box::new(bool::BOOL));
-    handlebars.register_helper("all", Box::new(bool::ALL));
-    handlebars.register_helper("any", Box::new(bool::ANY));
-
-    //number
-    handlebars.register_helper("num", Box::new(num::NUM));
-    handlebars.register_helper("num_add", Box::new(num::ADD));
-    handlebars.register_helper("num_sub", Box::new(num::SUB));
-    handlebars.register_helper("num_mul", Box::new(num::MUL));
-    handlebars.register_helper("num_div", Box::new(num::DIV));
+/// helpers built in handlebars
+static BUILTIN_HELPERS: &[&str] = &[
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
 
-    //array
-    handlebars.register_helper("arr", Box::new(arr::ARR));
-    handlebars.register_helper("arr_contains", Box::new(arr::CONTAINS));
-    handlebars.register_helper("arr_sub", Box::new(arr::SUB));
-    handlebars.register_helper("arr_len", Box::new(arr::LEN));
-    handlebars.register_helper("arr_get", Box::new(arr::GET));
+pub fn register(handlebars: &mut Handlebars) {
+    for (name, helper) in helper_vec() {
+        handlebars.register_helper(name, helper);
+    }
+}
 
-    //string
-    handlebars.register_helper("str", Box::new(str::STR));
-    handlebars.register_helper("str_sub", Box::new(str::SUB));
-    handlebars.register_helper("str_len", Box::new(str::LEN));
-    handlebars.register_helper("str_escape", Box::new(str::ESCAPE));
-    handlebars.register_helper("str_contains", Box::new(str::CONTAINS));
-    handlebars.register_helper("str_start_with", Box::new(str::START_WITH));
-    handlebars.register_helper("str_end_with", Box::new(str::END_WITH));
+/// names of every helper a template may call
+pub fn name_vec() -> Vec<&'static str> {
+    let mut name_vec = BUILTIN_HELPERS.to_vec();
+    name_vec.extend(helper_vec().into_iter().map(|(name, _)| name));
+    name_vec
+}
 
-    //fs
-    handlebars.register_helper("fs_read", Box::new(fs::READ));
-    handlebars.register_helper("fs_path", Box::new(fs::PATH));
+fn helper_vec() -> Vec<(&'static str, Box<dyn HelperDef + Send + Sync>)> {
+    vec![
+        //handlebars-3.5.4/src/registry.rs:118
+        //literal
+        (
+            "bl",
+            Box::new(LiteralHelper {
+                literal: "{".into(),
+            }),
+        ),
+        (
+            "br",
+            Box::new(LiteralHelper {
+                literal: "}".into(),
+            }),
+        ),
+        //json
+        ("json", Box::new(json::JSON)),
+        ("json_path", Box::new(json::PATH)),
+        //object
+        ("obj", Box::new(obj::OBJ)),
+        ("obj_contains_key", Box::new(obj::OBJ_CONTAINS_KEY)),
+        // bool
+        ("bool", Box::new(bool::BOOL)),
+        ("all", Box::new(bool::ALL)),
+        ("any", Box::new(bool::ANY)),
+        //number
+        ("num", Box::new(num::NUM)),
+        ("num_add", Box::new(num::ADD)),
+        ("num_sub", Box::new(num::SUB)),
+        ("num_mul", Box::new(num::MUL)),
+        ("num_div", Box::new(num::DIV)),
+        //array
+        ("arr", Box::new(arr::ARR)),
+        ("arr_contains", Box::new(arr::CONTAINS)),
+        ("arr_sub", Box::new(arr::SUB)),
+        ("arr_len", Box::new(arr::LEN)),
+        ("arr_get", Box::new(arr::GET)),
+        //string
+        ("str", Box::new(str::STR)),
+        ("str_sub", Box::new(str::SUB)),
+        ("str_len", Box::new(str::LEN)),
+        ("str_escape", Box::new(str::ESCAPE)),
+        ("str_contains", Box::new(str::CONTAINS)),
+        ("str_start_with", Box::new(str::START_WITH)),
+        ("str_end_with", Box::new(str::END_WITH)),
+        //fs
+        ("fs_read", Box::new(fs::READ)),
+        ("fs_path", Box::new(fs::PATH)),
+    ]
 }
 
 pub struct LiteralHelper {
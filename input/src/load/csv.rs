@@ -135,6 +135,11 @@ impl StageLoader for CsvStageLoader {
         }
         Ok(result)
     }
+
+    async fn fields(&mut self) -> Result<Option<Vec<String>>, Error> {
+        let header = self.reader.headers()?;
+        Ok(Some(header.iter().map(|h| h.to_string()).collect()))
+    }
 }
 
 async fn load<R: std::io::Read>(reader: &mut Reader<R>, size: usize) -> Result<Vec<Value>, Error> {
@@ -164,5 +169,7 @@ async fn load<R: std::io::Read>(reader: &mut Reader<R>, size: usize) -> Result<V
 }
 
 async fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader<File>, Error> {
-    Ok(ReaderBuilder::new().from_path(path)?)
+    ReaderBuilder::new()
+        .from_path(path.as_ref())
+        .map_err(|e| format!("{}: {}", path.as_ref().to_str().unwrap_or(""), e).into())
 }
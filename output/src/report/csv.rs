@@ -14,13 +14,15 @@ use chord_core::output::{async_trait, TaskReporter};
 use chord_core::output::Error;
 use chord_core::output::JobReporter;
 use chord_core::output::StageReporter;
-use chord_core::step::{ActionState, StepState};
+use chord_core::stat::{Stat, Statistic};
+use chord_core::step::{ActionState, StepAsset, StepState};
 use chord_core::task::{StageAsset, TaskAsset, TaskId, TaskState};
-use chord_core::value::{to_string_pretty, Value};
+use chord_core::value::{json, to_string_pretty, Value};
 
 pub struct CsvJobReporter {
     dir: PathBuf,
     with_bom: bool,
+    append: bool,
 }
 
 #[async_trait]
@@ -30,7 +32,9 @@ impl JobReporter for CsvJobReporter {
         task_id: Arc<dyn TaskId>,
         flow: Arc<Flow>,
     ) -> Result<Box<dyn TaskReporter>, Error> {
-        let reporter = CsvTaskReporter::new(self.dir.clone(), task_id, flow, self.with_bom).await?;
+        let reporter =
+            CsvTaskReporter::new(self.dir.clone(), task_id, flow, self.with_bom, self.append)
+                .await?;
         Ok(Box::new(reporter))
     }
 }
@@ -41,11 +45,12 @@ impl CsvJobReporter {
         name: String,
         exec_id: String,
         with_bom: bool,
+        append: bool,
     ) -> Result<CsvJobReporter, Error> {
         let report_dir = report_dir.as_ref().join(name).join(exec_id);
         if !exists(report_dir.as_path()).await {
             create_dir_all(report_dir.as_path()).await?;
-        } else {
+        } else if !append {
             let mut rd = read_dir(report_dir.clone()).await?;
             loop {
                 let rf: Option<DirEntry> = rd.next_entry().await?;
@@ -63,6 +68,7 @@ impl CsvJobReporter {
         Ok(CsvJobReporter {
             dir: report_dir.clone(),
             with_bom,
+            append,
         })
     }
 }
@@ -71,6 +77,7 @@ pub struct CsvTaskReporter {
     dir: PathBuf,
     task_id: Arc<dyn TaskId>,
     with_bom: bool,
+    append: bool,
     flow: Arc<Flow>,
 }
 
@@ -80,14 +87,24 @@ impl CsvTaskReporter {
         task_id: Arc<dyn TaskId>,
         flow: Arc<Flow>,
         with_bom: bool,
+        append: bool,
     ) -> Result<CsvTaskReporter, Error> {
         let dir = PathBuf::from(dir.as_ref());
+        if append {
+            for task_state_view in ["O", "E", "F", "C"] {
+                let report_file = dir.join(format!("{}.{}.csv", task_state_view, task_id.task()));
+                if exists(report_file.as_path()).await {
+                    remove_file(report_file).await?;
+                }
+            }
+        }
         let task_state_file = dir.join(format!("R.{}.csv", task_id.task()));
         from_path(task_state_file, with_bom, false).await?;
         let report = CsvTaskReporter {
             dir,
             task_id,
             with_bom,
+            append,
             flow,
         };
         Ok(report)
@@ -103,6 +120,7 @@ impl TaskReporter for CsvTaskReporter {
             stage_id,
             self.flow.clone(),
             self.with_bom,
+            self.append,
         )
             .await?;
         Ok(Box::new(reporter))
@@ -112,14 +130,41 @@ impl TaskReporter for CsvTaskReporter {
         Ok(())
     }
 
+    async fn post(&mut self, post_asset: &dyn CaseAsset) -> Result<(), Error> {
+        let report_file = self.dir.join(format!("post.{}.csv", self.task_id.task()));
+        let mut writer = from_path(report_file, self.with_bom, false).await?;
+        let head = head();
+        writer.write_record(&head)?;
+        for row in to_value_vec(post_asset, &head) {
+            writer.write_record(&row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
     async fn end(&mut self, task_asset: &dyn TaskAsset) -> Result<(), Error> {
         let task_state_view = match task_asset.state() {
             TaskState::Ok => "O",
             TaskState::Err(_) => "E",
             TaskState::Fail(_) => "F",
+            TaskState::Cancelled(_) => "C",
         };
 
         let report_file = self.dir.join(format!("R.{}.csv", self.task_id.task()));
+        let mut writer = from_path(report_file.as_path(), self.with_bom, false).await?;
+        writer.write_record(head())?;
+        for sr in stat_to_value_vec(
+            task_asset.stat(),
+            task_asset.id().task(),
+            "",
+            "",
+            task_asset.start(),
+            task_asset.end(),
+        ) {
+            writer.write_record(&sr)?;
+        }
+        writer.flush()?;
+
         let report_file_new =
             self.dir
                 .join(format!("{}.{}.csv", task_state_view, self.task_id.task()));
@@ -131,6 +176,8 @@ impl TaskReporter for CsvTaskReporter {
 pub struct CsvStageReporter {
     writer: Writer<std::fs::File>,
     head: Vec<String>,
+    task_id: Arc<dyn TaskId>,
+    stage_id: String,
 }
 
 impl CsvStageReporter {
@@ -140,21 +187,27 @@ impl CsvStageReporter {
         stage_id: &str,
         _flow: Arc<Flow>,
         with_bom: bool,
+        append: bool,
     ) -> Result<CsvStageReporter, Error> {
         let dir = PathBuf::from(dir.as_ref());
         let report_file = dir.join(format!("{}.{}.csv", task_id.task(), stage_id));
-        let mut writer: Writer<std::fs::File> =
-            from_path(report_file, with_bom, false).await?;
-
-        let head = vec![
-            "task", "stage", "stage_exec", "case", "step", "action", "frame", "layer", "start", "end", "state", "value", "explain",
-        ]
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect();
-        writer.write_record(&head)?;
+        let head = head();
+        let writer = if append && exists(report_file.as_path()).await {
+            let file = std::fs::OpenOptions::new().append(true).open(report_file)?;
+            csv::WriterBuilder::new().from_writer(file)
+        } else {
+            let mut writer: Writer<std::fs::File> =
+                from_path(report_file, with_bom, false).await?;
+            writer.write_record(&head)?;
+            writer
+        };
 
-        let report = CsvStageReporter { writer, head };
+        let report = CsvStageReporter {
+            writer,
+            head,
+            task_id,
+            stage_id: stage_id.to_string(),
+        };
         Ok(report)
     }
 }
@@ -165,6 +218,26 @@ impl StageReporter for CsvStageReporter {
         Ok(())
     }
 
+    async fn concurrency(&mut self, time: DateTime<Utc>, concurrency: usize) -> Result<(), Error> {
+        let cr = vec![
+            self.task_id.task().to_string(),
+            self.stage_id.clone(),
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            "concurrency".to_string(),
+            time.format("%T").to_string(),
+            time.format("%T").to_string(),
+            "O".to_string(),
+            concurrency.to_string(),
+            "".to_string(),
+        ];
+        self.writer.write_record(&cr)?;
+        Ok(())
+    }
+
     async fn report(&mut self, ca_vec: &Vec<Box<dyn CaseAsset>>) -> Result<(), Error> {
         if ca_vec.is_empty() {
             return Ok(());
@@ -172,12 +245,109 @@ impl StageReporter for CsvStageReporter {
         return Ok(report(&mut self.writer, ca_vec, &self.head).await?);
     }
 
-    async fn end(&mut self, _sa: &dyn StageAsset) -> Result<(), Error> {
+    async fn end(&mut self, sa: &dyn StageAsset) -> Result<(), Error> {
+        if let Some(arrival) = sa.arrival() {
+            let ar = vec![
+                sa.id().task().task().to_string(),
+                sa.id().stage().to_string(),
+                sa.id().exec().to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "arrival".to_string(),
+                sa.start().format("%T").to_string(),
+                sa.end().format("%T").to_string(),
+                "O".to_string(),
+                to_csv_string(&json!({
+                    "rate": arrival.rate(),
+                    "scheduled": arrival.scheduled(),
+                    "started": arrival.started(),
+                    "late": arrival.late(),
+                    "skipped": arrival.skipped(),
+                })),
+                "".to_string(),
+            ];
+            self.writer.write_record(&ar)?;
+        }
+        for sr in stat_to_value_vec(
+            sa.stat(),
+            sa.id().task().task(),
+            sa.id().stage(),
+            sa.id().exec(),
+            sa.start(),
+            sa.end(),
+        ) {
+            self.writer.write_record(&sr)?;
+        }
         self.writer.flush()?;
         Ok(())
     }
 }
 
+fn head() -> Vec<String> {
+    vec![
+        "task", "stage", "stage_exec", "case", "step", "action", "frame", "layer", "start", "end", "state", "value", "explain",
+    ]
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn stat_to_value_vec(
+    stat: &Statistic,
+    task: &str,
+    stage: &str,
+    stage_exec: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<Vec<String>> {
+    let mut case_value = stat_to_value(stat.case());
+    case_value["throughput"] = json!((stat.throughput() * 1000.0).round() / 1000.0);
+    let mut row_vec = vec![("", "", case_value, stat.case().fail())];
+    for (step, s) in stat.step_iter() {
+        row_vec.push((step, "", stat_to_value(s), s.fail()));
+    }
+    for (step, action, s) in stat.action_iter() {
+        row_vec.push((step, action, stat_to_value(s), s.fail()));
+    }
+
+    row_vec
+        .into_iter()
+        .map(|(step, action, value, fail)| {
+            vec![
+                task.to_string(),
+                stage.to_string(),
+                stage_exec.to_string(),
+                "".to_string(),
+                step.to_string(),
+                action.to_string(),
+                "".to_string(),
+                "stat".to_string(),
+                start.format("%T").to_string(),
+                end.format("%T").to_string(),
+                if fail == 0 { "O" } else { "F" }.to_string(),
+                to_csv_string(&value),
+                "".to_string(),
+            ]
+        })
+        .collect()
+}
+
+/// latency in milliseconds
+fn stat_to_value(stat: &Stat) -> Value {
+    let ms = |d: std::time::Duration| d.as_micros() as f64 / 1000.0;
+    let h = stat.histogram();
+    json!({
+        "ok": stat.ok(),
+        "fail": stat.fail(),
+        "p50": ms(h.p50()),
+        "p90": ms(h.p90()),
+        "p99": ms(h.p99()),
+        "max": ms(h.max()),
+    })
+}
+
 async fn from_path<P: AsRef<Path>>(
     path: P,
     with_bom: bool,
@@ -217,140 +387,20 @@ fn to_value_vec(ca: &dyn CaseAsset, _header: &Vec<String>) -> Vec<Vec<String>> {
         CaseState::Ok(sa_vec)
         | CaseState::Fail(sa_vec) => {
             for sa in sa_vec.iter() {
-                match sa.state() {
-                    StepState::Ok(aa_vec)
-                    | StepState::Fail(aa_vec) => {
-                        for aa in aa_vec.iter() {
-                            match aa.state() {
-                                ActionState::Ok(a) => {
-                                    match a {
-                                        Asset::Value(v) => {
-                                            let aar = vec![
-                                                sa.id().case().stage().task().task().to_string(),
-                                                sa.id().case().stage().stage().to_string(),
-                                                sa.id().case().stage().exec().to_string(),
-                                                sa.id().case().case().to_string(),
-                                                sa.id().step().to_string(),
-                                                aa.id().to_string(),
-                                                "".to_string(),
-                                                "action".to_string(),
-                                                aa.start().format("%T").to_string(),
-                                                aa.end().format("%T").to_string(),
-                                                "O".to_string(),
-                                                to_csv_string(v),
-                                                to_csv_string(aa.explain()),
-                                            ];
-                                            result_vec.push(aar);
-                                        }
-                                        Asset::Data(d) => {
-                                            let aar = vec![
-                                                sa.id().case().stage().task().task().to_string(),
-                                                sa.id().case().stage().stage().to_string(),
-                                                sa.id().case().stage().exec().to_string(),
-                                                sa.id().case().case().to_string(),
-                                                sa.id().step().to_string(),
-                                                aa.id().to_string(),
-                                                "".to_string(),
-                                                "action".to_string(),
-                                                aa.start().format("%T").to_string(),
-                                                aa.end().format("%T").to_string(),
-                                                "O".to_string(),
-                                                to_csv_string(&d.to_value()),
-                                                to_csv_string(aa.explain()),
-                                            ];
-                                            result_vec.push(aar);
-                                        }
-                                        Asset::Frames(fv) => {
-                                            for f in fv {
-                                                let aar = vec![
-                                                    sa.id().case().stage().task().task().to_string(),
-                                                    sa.id().case().stage().stage().to_string(),
-                                                    sa.id().case().stage().exec().to_string(),
-                                                    sa.id().case().case().to_string(),
-                                                    sa.id().step().to_string(),
-                                                    aa.id().to_string(),
-                                                    f.id().to_string(),
-                                                    "frame".to_string(),
-                                                    f.start().format("%T").to_string(),
-                                                    f.end().format("%T").to_string(),
-                                                    "O".to_string(),
-                                                    to_csv_string(&f.to_value()),
-                                                    "".to_string(),
-                                                ];
-                                                result_vec.push(aar);
-                                            }
-
-                                            let aar = vec![
-                                                sa.id().case().stage().task().task().to_string(),
-                                                sa.id().case().stage().stage().to_string(),
-                                                sa.id().case().stage().exec().to_string(),
-                                                sa.id().case().case().to_string(),
-                                                sa.id().step().to_string(),
-                                                aa.id().to_string(),
-                                                "".to_string(),
-                                                "action".to_string(),
-                                                aa.start().format("%T").to_string(),
-                                                aa.end().format("%T").to_string(),
-                                                "O".to_string(),
-                                                "".to_string(),
-                                                to_csv_string(aa.explain()),
-                                            ];
-                                            result_vec.push(aar);
-                                        }
-                                    }
-                                }
-                                ActionState::Err(e) => {
-                                    let aar = vec![
-                                        sa.id().case().stage().task().task().to_string(),
-                                        sa.id().case().stage().stage().to_string(),
-                                        sa.id().case().stage().exec().to_string(),
-                                        sa.id().case().case().to_string(),
-                                        sa.id().step().to_string(),
-                                        aa.id().to_string(),
-                                        "".to_string(),
-                                        "action".to_string(),
-                                        aa.start().format("%T").to_string(),
-                                        aa.end().format("%T").to_string(),
-                                        "E".to_string(),
-                                        e.to_string(),
-                                        to_csv_string(aa.explain()),
-                                    ];
-                                    result_vec.push(aar);
-                                }
-                            }
-                        }
-                    }
-                };
-
-                let sas = match sa.state() {
-                    StepState::Ok(_) => "O".to_string(),
-                    StepState::Fail(_) => "F".to_string(),
-                };
-                let sar = vec![
-                    sa.id().case().stage().task().task().to_string(),
-                    sa.id().case().stage().stage().to_string(),
-                    sa.id().case().stage().exec().to_string(),
-                    sa.id().case().case().to_string(),
-                    sa.id().step().to_string(),
-                    "".to_string(),
-                    "".to_string(),
-                    "step".to_string(),
-                    sa.start().format("%T").to_string(),
-                    sa.end().format("%T").to_string(),
-                    sas,
-                    "".to_string(),
-                    "".to_string(),
-                ];
-                result_vec.push(sar);
+                step_to_value_vec(sa.as_ref(), "step", &mut result_vec);
             }
         }
 
-        CaseState::Err(_) => {}
+        CaseState::Err(_) | CaseState::Cancelled(_) => {}
     };
+    for sa in ca.defer().iter() {
+        step_to_value_vec(sa.as_ref(), "defer", &mut result_vec);
+    }
     let cas = match ca.state() {
         CaseState::Ok(_) => "O".to_string(),
         CaseState::Err(_) => "E".to_string(),
         CaseState::Fail(_) => "F".to_string(),
+        CaseState::Cancelled(_) => "C".to_string(),
     };
 
     let car = vec![
@@ -373,6 +423,136 @@ fn to_value_vec(ca: &dyn CaseAsset, _header: &Vec<String>) -> Vec<Vec<String>> {
     result_vec
 }
 
+fn step_to_value_vec(sa: &dyn StepAsset, layer: &str, result_vec: &mut Vec<Vec<String>>) {
+    match sa.state() {
+        StepState::Ok(aa_vec)
+        | StepState::Fail(aa_vec) => {
+            for aa in aa_vec.iter() {
+                match aa.state() {
+                    ActionState::Ok(a) => {
+                        match a {
+                            Asset::Value(v) => {
+                                let aar = vec![
+                                    sa.id().case().stage().task().task().to_string(),
+                                    sa.id().case().stage().stage().to_string(),
+                                    sa.id().case().stage().exec().to_string(),
+                                    sa.id().case().case().to_string(),
+                                    sa.id().step().to_string(),
+                                    aa.id().to_string(),
+                                    "".to_string(),
+                                    "action".to_string(),
+                                    aa.start().format("%T").to_string(),
+                                    aa.end().format("%T").to_string(),
+                                    "O".to_string(),
+                                    to_csv_string(v),
+                                    to_csv_string(aa.explain()),
+                                ];
+                                result_vec.push(aar);
+                            }
+                            Asset::Data(d) => {
+                                let aar = vec![
+                                    sa.id().case().stage().task().task().to_string(),
+                                    sa.id().case().stage().stage().to_string(),
+                                    sa.id().case().stage().exec().to_string(),
+                                    sa.id().case().case().to_string(),
+                                    sa.id().step().to_string(),
+                                    aa.id().to_string(),
+                                    "".to_string(),
+                                    "action".to_string(),
+                                    aa.start().format("%T").to_string(),
+                                    aa.end().format("%T").to_string(),
+                                    "O".to_string(),
+                                    to_csv_string(&d.to_value()),
+                                    to_csv_string(aa.explain()),
+                                ];
+                                result_vec.push(aar);
+                            }
+                            Asset::Frames(fv) => {
+                                for f in fv {
+                                    let aar = vec![
+                                        sa.id().case().stage().task().task().to_string(),
+                                        sa.id().case().stage().stage().to_string(),
+                                        sa.id().case().stage().exec().to_string(),
+                                        sa.id().case().case().to_string(),
+                                        sa.id().step().to_string(),
+                                        aa.id().to_string(),
+                                        f.id().to_string(),
+                                        "frame".to_string(),
+                                        f.start().format("%T").to_string(),
+                                        f.end().format("%T").to_string(),
+                                        "O".to_string(),
+                                        to_csv_string(&f.to_value()),
+                                        "".to_string(),
+                                    ];
+                                    result_vec.push(aar);
+                                }
+
+                                let aar = vec![
+                                    sa.id().case().stage().task().task().to_string(),
+                                    sa.id().case().stage().stage().to_string(),
+                                    sa.id().case().stage().exec().to_string(),
+                                    sa.id().case().case().to_string(),
+                                    sa.id().step().to_string(),
+                                    aa.id().to_string(),
+                                    "".to_string(),
+                                    "action".to_string(),
+                                    aa.start().format("%T").to_string(),
+                                    aa.end().format("%T").to_string(),
+                                    "O".to_string(),
+                                    "".to_string(),
+                                    to_csv_string(aa.explain()),
+                                ];
+                                result_vec.push(aar);
+                            }
+                        }
+                    }
+                    ActionState::Err(e) => {
+                        let aar = vec![
+                            sa.id().case().stage().task().task().to_string(),
+                            sa.id().case().stage().stage().to_string(),
+                            sa.id().case().stage().exec().to_string(),
+                            sa.id().case().case().to_string(),
+                            sa.id().step().to_string(),
+                            aa.id().to_string(),
+                            "".to_string(),
+                            "action".to_string(),
+                            aa.start().format("%T").to_string(),
+                            aa.end().format("%T").to_string(),
+                            if aa.state().is_timeout() { "T" } else { "E" }.to_string(),
+                            e.to_string(),
+                            to_csv_string(aa.explain()),
+                        ];
+                        result_vec.push(aar);
+                    }
+                }
+            }
+        }
+        StepState::Skipped => {}
+    };
+
+    let sas = match sa.state() {
+        StepState::Ok(_) => "O".to_string(),
+        StepState::Fail(_) => "F".to_string(),
+        StepState::Skipped => "S".to_string(),
+    };
+    let sar = vec![
+        sa.id().case().stage().task().task().to_string(),
+        sa.id().case().stage().stage().to_string(),
+        sa.id().case().stage().exec().to_string(),
+        sa.id().case().case().to_string(),
+        sa.id().step().to_string(),
+        "".to_string(),
+        "".to_string(),
+        layer.to_string(),
+        sa.start().format("%T").to_string(),
+        sa.end().format("%T").to_string(),
+        sas,
+        "".to_string(),
+        "".to_string(),
+    ];
+    result_vec.push(sar);
+}
+
 fn to_csv_string(explain: &Value) -> String {
     if explain.is_string() {
         return explain.as_str().unwrap().to_string();
@@ -35,6 +35,7 @@ impl DefaultJobReporter {
         conf: Option<&Value>,
         name: &str,
         exec_id: &str,
+        append: bool,
     ) -> Result<DefaultJobReporter, Error> {
         match conf {
             None => {
@@ -59,6 +60,7 @@ impl DefaultJobReporter {
                             name.to_string(),
                             exec_id.to_string(),
                             v["with_bom"].as_bool().unwrap_or(true),
+                            append,
                         )
                             .await?;
                         return Ok(DefaultJobReporter {
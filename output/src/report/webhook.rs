@@ -200,12 +200,14 @@ fn ta_doc(task_id: &dyn TaskId, start: DateTime<Utc>, end: DateTime<Utc>, ts: &T
             TaskState::Ok => "O",
             TaskState::Fail(_) => "F",
             TaskState::Err(_) => "E",
+            TaskState::Cancelled(_) => "C",
         }
             .to_owned(),
         value: match ts {
             TaskState::Ok => Value::Null,
             TaskState::Fail(_) => Value::Null,
             TaskState::Err(e) => Value::String(e.to_string()),
+            TaskState::Cancelled(c) => Value::String(c.to_string()),
         },
     }
 }
@@ -243,6 +245,7 @@ fn sa_doc(sa: &dyn StepAsset) -> Data {
         state: match sa.state() {
             StepState::Ok(_) => "O",
             StepState::Fail(_) => "F",
+            StepState::Skipped => "S",
         }
             .to_owned(),
         value: match sa.state() {
@@ -254,6 +257,7 @@ fn sa_doc(sa: &dyn StepAsset) -> Data {
                 //todo
                 Value::Null
             }
+            StepState::Skipped => Value::Null,
         },
     }
 }